@@ -136,7 +136,10 @@ fn main() -> Result<(), Error> {
     for y in 0..map.len() / 20 {
         for x in 0..20 {
             let tile = map[y * 20 + x];
-            if tile >= 1 {
+            if tile == 3 {
+                // The door sits between a north and a south wall
+                world.set_door(x as i32, y as i32, Door::new(tiles[tile - 1].clone(), true));
+            } else if tile >= 1 {
                 world.set_wall(x as i32, y as i32, tiles[tile - 1].clone());
             }
         }
@@ -156,8 +159,8 @@ fn main() -> Result<(), Error> {
 
     // Torch Sprite
     let mut sprite = Sprite::new(4.1, 6.1, Tile::textured_anim(image_id, calc_tile_rect(14, 14, 24,), 2));
-    sprite.shrink = 2; // Scale the sprite down
-    sprite.move_y = -100.0; // Move the sprite up
+    sprite.set_scale(0.5); // Scale the sprite down, scale_x and scale_y set the width and height separately
+    sprite.z = 0.45; // Move the sprite up, the height above the floor is in world units and the same at any resolution
     world.add_sprite(sprite);
     world.add_light(4, 6, 2); // Add a light source at the torch position
 
@@ -230,6 +233,12 @@ fn main() -> Result<(), Error> {
                         Key::ArrowDown => {
                             caster.go_backward(&world);
                         },
+                        Key::Space => {
+                            // Toggle the door in the cell in front of the player
+                            let (x, y) = caster.get_pos();
+                            let (dir_x, dir_y) = caster.get_dir();
+                            world.toggle_door((x + dir_x).floor() as i32, (y + dir_y).floor() as i32);
+                        },
                         _ => (),
                     }
                 }
@@ -264,7 +273,8 @@ fn main() -> Result<(), Error> {
             Event::RedrawRequested(_) => {
 
                 let frame = pixels.get_frame_mut();
-                caster.render(&mut frame[..], (0, 0, width, height), width, &mut world);
+                caster.tick(&mut world);
+                caster.render(&mut frame[..], (0, 0, width, height), width, &world);
 
                 if pixels
                     .render()
@@ -0,0 +1,78 @@
+/// How an animation plays the frames of a tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayMode {
+    /// Plays the frames once and stops at the last frame
+    Once,
+    /// Starts over after the last frame
+    Loop,
+    /// Plays the frames forward and then backward again
+    PingPong,
+}
+
+/// The timing of an animated tile, lets a sprite animate independently of the global animation time of the Raycaster
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+
+    /// The duration of one frame in seconds
+    pub frame_time          : f32,
+
+    /// Start offset in seconds, positive values start the animation further in, negative values delay it
+    pub offset              : f32,
+
+    pub mode                : PlayMode,
+
+    /// Seconds since the animation was started
+    pub time                : f32,
+}
+
+/// An animation
+impl Animation {
+    /// Creates a new animation with the given frame duration in seconds
+    pub fn new(frame_time: f32, mode: PlayMode) -> Self {
+        Self {
+            frame_time,
+            offset      : 0.0,
+            mode,
+            time        : 0.0,
+        }
+    }
+
+    /// Advances the animation by delta seconds
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+    }
+
+    /// Starts the animation again from the beginning
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+
+    /// Returns the frame to show of a tile with the given number of frames
+    pub fn get_frame(&self, frames: u16) -> usize {
+        let frames = frames.max(1) as usize;
+        if self.frame_time <= 0.0 {
+            return 0;
+        }
+
+        let step = ((self.time + self.offset).max(0.0) / self.frame_time) as usize;
+
+        match self.mode {
+            PlayMode::Once => step.min(frames - 1),
+            PlayMode::Loop => step % frames,
+            PlayMode::PingPong => {
+                if frames == 1 {
+                    return 0;
+                }
+                // forward over all frames and backward without repeating the first and last frame
+                let period = 2 * (frames - 1);
+                let step = step % period;
+                if step < frames { step } else { period - step }
+            }
+        }
+    }
+
+    /// Returns true if a play once animation has shown its last frame for the full frame time. Looping animations never finish.
+    pub fn is_finished(&self, frames: u16) -> bool {
+        self.mode == PlayMode::Once && self.time + self.offset >= frames.max(1) as f32 * self.frame_time
+    }
+}
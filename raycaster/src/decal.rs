@@ -0,0 +1,57 @@
+use crate::prelude::*;
+
+/// The surface of a cell a decal is attached to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Surface {
+    /// The floor of the cell
+    Floor,
+    /// The given face of the wall in the cell
+    Wall(Face),
+}
+
+/// A flat sprite fixed to a wall face or lying on the floor, like a painting, a switch or a blood splat.
+/// Decals are drawn on top of the surface and hidden by everything in front of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decal {
+
+    pub tile                : Tile,
+
+    pub surface             : Surface,
+
+    /// The area covered by the decal as (x, y, width, height) in cell units.
+    /// On a wall x runs from left to right as seen from the front of the face and y is the height above the floor of the bottom edge.
+    /// On the floor x and y are the offsets inside the cell, the top edge of the texture points north (+y).
+    pub rect                : (f32, f32, f32, f32),
+}
+
+/// A decal
+impl Decal {
+    /// Creates a decal on the given wall face covering the given rect
+    pub fn wall(face: Face, tile: Tile, rect: (f32, f32, f32, f32)) -> Self {
+        Self {
+            tile,
+            surface     : Surface::Wall(face),
+            rect,
+        }
+    }
+
+    /// Creates a decal on the floor covering the given rect of the cell
+    pub fn floor(tile: Tile, rect: (f32, f32, f32, f32)) -> Self {
+        Self {
+            tile,
+            surface     : Surface::Floor,
+            rect,
+        }
+    }
+
+    /// Returns the position inside the decal, from 0.0 to 1.0, for the given position on its surface or None if the position is outside
+    pub fn get_uv(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let u = (x - self.rect.0) / self.rect.2;
+        let v = (y - self.rect.1) / self.rect.3;
+        if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) {
+            Some((u, v))
+        } else {
+            None
+        }
+    }
+}
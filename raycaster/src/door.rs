@@ -0,0 +1,118 @@
+use crate::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Door {
+
+    pub tile                : Tile,
+
+    /// If true the door runs along the y axis and is passed through in x direction (i.e. it sits between a north and a south wall).
+    /// Otherwise the door runs along the x axis and is passed through in y direction.
+    pub vertical            : bool,
+
+    /// How far the door is slid open, 0.0 is fully closed, 1.0 is fully open
+    pub open                : f32,
+
+    /// The speed of the door in cells per second
+    pub speed               : f32,
+
+    pub state               : DoorState,
+}
+
+/// A sliding door
+impl Door {
+    /// Creates a new closed door
+    pub fn new(tile: Tile, vertical: bool) -> Self {
+        Self {
+            tile,
+            vertical,
+            open        : 0.0,
+            speed       : 1.0,
+            state       : DoorState::Closed,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    /// Checks if a DDA ray inside the door cell at map hits the closed part of the door, which is recessed half a cell.
+    /// The side distances and the side are the state of the DDA after stepping into the cell.
+    /// Returns the distance along the ray (the perpendicular distance for camera rays) and the x coordinate on the door texture.
+    pub fn hit_ray(&self, map: (i32, i32), pos: (f32, f32), ray_dir: (f32, f32), side_dist: (f32, f32), delta_dist: (f32, f32), side: i32) -> Option<(f32, f32)> {
+
+        // distances at which the ray enters and leaves the cell
+        let enter = if side == 0 { side_dist.0 - delta_dist.0 } else { side_dist.1 - delta_dist.1 };
+        let leave = side_dist.0.min(side_dist.1);
+
+        let (dist, door_x) = if self.vertical {
+            if ray_dir.0 == 0.0 { return None; }
+            let dist = (map.0 as f32 + 0.5 - pos.0) / ray_dir.0;
+            (dist, pos.1 + dist * ray_dir.1 - map.1 as f32)
+        } else {
+            if ray_dir.1 == 0.0 { return None; }
+            let dist = (map.1 as f32 + 0.5 - pos.1) / ray_dir.1;
+            (dist, pos.0 + dist * ray_dir.0 - map.0 as f32)
+        };
+
+        // the door slides towards the positive axis, the open part lets the ray pass
+        if dist < enter || dist > leave || !(self.open..1.0).contains(&door_x) {
+            return None;
+        }
+
+        Some((dist, door_x - self.open))
+    }
+
+    /// Starts opening the door
+    pub fn open(&mut self) {
+        if self.state != DoorState::Open {
+            self.state = DoorState::Opening;
+        }
+    }
+
+    /// Starts closing the door
+    pub fn close(&mut self) {
+        if self.state != DoorState::Closed {
+            self.state = DoorState::Closing;
+        }
+    }
+
+    /// Opens a closed or closing door, closes an open or opening one
+    pub fn toggle(&mut self) {
+        match self.state {
+            DoorState::Closed | DoorState::Closing => self.state = DoorState::Opening,
+            DoorState::Open | DoorState::Opening => self.state = DoorState::Closing,
+        }
+    }
+
+    /// Advances the door animation by delta seconds
+    pub fn update(&mut self, delta: f32) {
+        match self.state {
+            DoorState::Opening => {
+                self.open += self.speed * delta;
+                if self.open >= 1.0 {
+                    self.open = 1.0;
+                    self.state = DoorState::Open;
+                }
+            },
+            DoorState::Closing => {
+                self.open -= self.speed * delta;
+                if self.open <= 0.0 {
+                    self.open = 0.0;
+                    self.state = DoorState::Closed;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Returns true if the door is fully open and can be walked through
+    pub fn is_passable(&self) -> bool {
+        self.state == DoorState::Open
+    }
+}
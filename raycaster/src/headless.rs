@@ -0,0 +1,49 @@
+use crate::prelude::*;
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// Writes an RGBA buffer of the given size as a PNG file
+pub fn save_png(path: &Path, buffer: &[u8], width: usize, height: usize) -> io::Result<()> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(buffer).map_err(io::Error::other)
+}
+
+/// Loads an 8 bit RGBA or RGB PNG file and returns the RGBA buffer, the width and the height
+pub fn load_png(path: &Path) -> io::Result<(Vec<u8>, u32, u32)> {
+    let file = File::open(path)?;
+
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let mut reader = decoder.read_info().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    buf.truncate(info.buffer_size());
+
+    match (info.color_type, info.bit_depth) {
+        (png::ColorType::Rgba, png::BitDepth::Eight) => Ok((buf, info.width, info.height)),
+        (png::ColorType::Rgb, png::BitDepth::Eight) => {
+            let mut rgba = Vec::with_capacity(buf.len() / 3 * 4);
+            for rgb in buf.chunks_exact(3) {
+                rgba.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+            }
+            Ok((rgba, info.width, info.height))
+        },
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Only 8 bit RGBA and RGB images are supported")),
+    }
+}
+
+/// Headless rendering into PNG files
+impl Raycaster {
+    /// Renders the world map into an image of the given size and writes it as a PNG file
+    pub fn render_to_png(&self, path: &Path, width: usize, height: usize, world: &WorldMap) -> io::Result<()> {
+        let buffer = self.render_to_buffer(width, height, world);
+        save_png(path, &buffer, width, height)
+    }
+}
@@ -4,11 +4,26 @@ pub mod math;
 pub mod tile;
 pub mod sprite;
 pub mod light;
+pub mod door;
+pub mod wall;
+pub mod texture;
+pub mod animation;
+pub mod decal;
+pub mod ray;
+#[cfg(feature = "png")]
+pub mod headless;
 
 pub use crate::worldmap::WorldMap as WorldMap;
 pub use crate::tile::Tile as Tile;
 pub use crate::sprite::Sprite as Sprite;
+pub use crate::sprite::SpriteId as SpriteId;
 pub use crate::light::Light as Light;
+pub use crate::door::Door as Door;
+pub use crate::wall::Wall as Wall;
+pub use crate::decal::Decal as Decal;
+pub use crate::ray::RayHit as RayHit;
+pub use crate::texture::Filter as Filter;
+pub use crate::animation::Animation as Animation;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum WidgetKey {
@@ -30,6 +45,12 @@ pub mod prelude {
     pub use crate::worldmap::WorldMap;
     pub use crate::math::vec2;
     pub use crate::tile::Tile;
-    pub use crate::sprite::Sprite;
+    pub use crate::sprite::{Sprite, SpriteId};
     pub use crate::light::Light;
+    pub use crate::door::{Door, DoorState};
+    pub use crate::wall::{Wall, Face};
+    pub use crate::decal::{Decal, Surface};
+    pub use crate::ray::{RayHit, WallHit, SpriteHit, PickHit, PickTarget};
+    pub use crate::texture::Filter;
+    pub use crate::animation::{Animation, PlayMode};
 }
\ No newline at end of file
@@ -4,14 +4,41 @@
 pub struct Light {
 
     pub intensity           : i32,
+
+    /// The color of the light, the alpha value is ignored
+    pub color               : [u8;4],
+
+    /// The maximum random change of the light strength, 0.0 for a steady light
+    pub flicker_amplitude   : f32,
+
+    /// How many times per second the flicker changes
+    pub flicker_frequency   : f32,
+
+    /// The current flicker offset
+    /// Only used internally
+    pub flicker             : f32,
+
+    /// Seconds since the last flicker change
+    /// Only used internally
+    pub flicker_time        : f32,
 }
 
 /// A tile
 impl Light {
-    /// Creates a new sprite
+    /// Creates a new white light
     pub fn new(intensity: i32) -> Self {
+        Self::colored(intensity, [255, 255, 255, 255])
+    }
+
+    /// Creates a new light with the given color
+    pub fn colored(intensity: i32, color: [u8;4]) -> Self {
         Self {
             intensity,
+            color,
+            flicker_amplitude   : 0.15,
+            flicker_frequency   : 4.0,
+            flicker             : 0.0,
+            flicker_time        : 0.0,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,239 @@
+use crate::prelude::*;
+
+/// The number of cells the renderer steps through per column, pass it to WorldMap::trace_ray() to only hit walls which can be drawn
+pub const MAX_RAY_STEPS: usize = 40;
+
+/// A wall or door hit by a query ray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+
+    /// The cell of the wall or door
+    pub cell                : (i32, i32),
+
+    /// The face of the cell hit by the ray, for doors the side of the door facing the ray
+    pub face                : Face,
+
+    /// The exact hit position in world coordinates
+    pub point               : (f32, f32),
+
+    /// The distance from the origin of the ray to the hit position
+    pub distance            : f32,
+
+    /// True if the closed part of a door was hit
+    pub door                : bool,
+}
+
+/// A sprite hit by a query ray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteHit {
+
+    pub id                  : SpriteId,
+
+    /// The position where the ray enters the sprite
+    pub point               : (f32, f32),
+
+    /// The distance from the origin of the ray to the hit position
+    pub distance            : f32,
+}
+
+/// The result of a query ray, the sprite is only set if it is hit before the wall
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RayHit {
+    pub wall                : Option<WallHit>,
+    pub sprite              : Option<SpriteHit>,
+}
+
+/// What is drawn at a picked pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PickTarget {
+    /// The given face of a wall
+    Wall(Face),
+    /// The closed part of a door
+    Door,
+    Floor,
+    /// The ceiling, also for cells with an open ceiling which show the sky
+    Ceiling,
+    Sprite(SpriteId),
+}
+
+/// The result of Raycaster::pick()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickHit {
+
+    pub target              : PickTarget,
+
+    /// The cell of the wall, door, floor or ceiling, or the cell the sprite stands in
+    pub cell                : (i32, i32),
+
+    /// The world position of the pixel as (x, y, height above the floor)
+    pub position            : (f32, f32, f32),
+
+    /// The perpendicular distance from the camera plane, as used for the z-buffer
+    pub distance            : f32,
+}
+
+/// Ray queries for gameplay code
+impl WorldMap {
+    /// Casts a ray from the origin in the given direction, which does not need to be normalized, and returns the first wall or closed door
+    /// and the first sprite in front of it within max_distance. Transparent walls are hit like all other walls.
+    /// Sprites are treated as upright cylinders with a diameter of their width, sprites containing the origin are ignored so that a sprite can cast from its own position.
+    pub fn cast_ray(&self, origin: (f32, f32), dir: (f32, f32), max_distance: f32) -> RayHit {
+        let wall = self.cast_wall_ray(origin, dir, max_distance, false);
+
+        let max_distance = wall.map(|hit| hit.distance).unwrap_or(max_distance);
+        let sprite = self.cast_sprite_ray(origin, dir, max_distance);
+
+        RayHit { wall, sprite }
+    }
+
+    /// Returns true if nothing blocks the sight from a to b, transparent walls and the open part of doors do not block the sight. Sprites are ignored.
+    pub fn line_of_sight(&self, a: (f32, f32), b: (f32, f32)) -> bool {
+        let dir = (b.0 - a.0, b.1 - a.1);
+        let distance = (dir.0 * dir.0 + dir.1 * dir.1).sqrt();
+        if distance == 0.0 {
+            return true;
+        }
+
+        self.cast_wall_ray(a, dir, distance, true).is_none()
+    }
+
+    /// Steps a ray from the origin through the cells with the DDA of the renderer and calls f for every wall and closed door hit, nearest first,
+    /// until f returns false, the ray passes max_distance or it stepped through max_steps cells. The distances are measured in multiples of the length of dir,
+    /// for a camera ray (the view direction plus a part of the camera plane) these are the perpendicular distances used for the projection.
+    pub fn trace_ray<F: FnMut(&WallHit) -> bool>(&self, origin: (f32, f32), dir: (f32, f32), max_distance: f32, max_steps: usize, mut f: F) {
+        if dir == (0.0, 0.0) {
+            return;
+        }
+
+        let mut map_x = origin.0.floor() as i32;
+        let mut map_y = origin.1.floor() as i32;
+
+        let delta_dist_x = if dir.0 == 0.0 { f32::MAX } else { (1.0 / dir.0).abs() };
+        let delta_dist_y = if dir.1 == 0.0 { f32::MAX } else { (1.0 / dir.1).abs() };
+
+        let (step_x, mut side_dist_x) = if dir.0 < 0.0 {
+            (-1, (origin.0 - map_x as f32) * delta_dist_x)
+        } else {
+            (1, (map_x as f32 + 1.0 - origin.0) * delta_dist_x)
+        };
+
+        let (step_y, mut side_dist_y) = if dir.1 < 0.0 {
+            (-1, (origin.1 - map_y as f32) * delta_dist_y)
+        } else {
+            (1, (map_y as f32 + 1.0 - origin.1) * delta_dist_y)
+        };
+
+        for _ in 0..max_steps {
+            // jump to next map square, either in x-direction, or in y-direction
+            let side = if side_dist_x < side_dist_y {
+                side_dist_x += delta_dist_x;
+                map_x += step_x;
+                0
+            } else {
+                side_dist_y += delta_dist_y;
+                map_y += step_y;
+                1
+            };
+
+            // the distance at which the ray enters the cell
+            let enter = if side == 0 { side_dist_x - delta_dist_x } else { side_dist_y - delta_dist_y };
+            if enter > max_distance {
+                return;
+            }
+
+            let mut hit = None;
+
+            if self.has_wall(map_x, map_y) {
+                hit = Some((enter, Face::from_ray(side, step_x, step_y), false));
+            } else if let Some(door) = self.get_door(map_x, map_y) {
+                if let Some((distance, _)) = door.hit_ray((map_x, map_y), origin, dir, (side_dist_x, side_dist_y), (delta_dist_x, delta_dist_y), side) {
+                    let door_side = if door.vertical { 0 } else { 1 };
+                    hit = Some((distance, Face::from_ray(door_side, step_x, step_y), true));
+                }
+            }
+
+            if let Some((distance, face, door)) = hit {
+                if distance > max_distance {
+                    return;
+                }
+
+                let hit = WallHit {
+                    cell        : (map_x, map_y),
+                    face,
+                    point       : (origin.0 + dir.0 * distance, origin.1 + dir.1 * distance),
+                    distance,
+                    door,
+                };
+
+                if !f(&hit) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the first wall or closed door hit by the ray within max_distance, transparent walls are skipped if see_through is set
+    fn cast_wall_ray(&self, origin: (f32, f32), dir: (f32, f32), max_distance: f32, see_through: bool) -> Option<WallHit> {
+        let length = (dir.0 * dir.0 + dir.1 * dir.1).sqrt();
+        if length == 0.0 {
+            return None;
+        }
+
+        // with a normalized direction the distances are the distances along the ray
+        let dir = (dir.0 / length, dir.1 / length);
+
+        // the number of cell borders the ray crosses within max_distance
+        let max_steps = (max_distance * (dir.0.abs() + dir.1.abs())).min(u32::MAX as f32) as usize + 2;
+
+        let mut result = None;
+        self.trace_ray(origin, dir, max_distance, max_steps, |hit| {
+            if see_through && self.get_wall_cell(hit.cell.0, hit.cell.1).is_some_and(|wall| wall.transparent) {
+                return true;
+            }
+            result = Some(*hit);
+            false
+        });
+
+        result
+    }
+
+    /// Returns the closest sprite hit by the ray within max_distance
+    fn cast_sprite_ray(&self, origin: (f32, f32), dir: (f32, f32), max_distance: f32) -> Option<SpriteHit> {
+        let length = (dir.0 * dir.0 + dir.1 * dir.1).sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        let ray_dir = (dir.0 / length, dir.1 / length);
+
+        let mut closest : Option<SpriteHit> = None;
+
+        for (id, sprite) in self.sprites() {
+            let radius = sprite.scale_x / 2.0;
+            let to_sprite = (sprite.x - origin.0, sprite.y - origin.1);
+
+            // the distance along the ray to the point closest to the center of the sprite
+            let along = to_sprite.0 * ray_dir.0 + to_sprite.1 * ray_dir.1;
+            let center_dist = to_sprite.0 * to_sprite.0 + to_sprite.1 * to_sprite.1;
+            if center_dist <= radius * radius {
+                continue;
+            }
+
+            let miss = center_dist - along * along;
+            if along <= 0.0 || miss > radius * radius {
+                continue;
+            }
+
+            let distance = along - (radius * radius - miss).sqrt();
+            let closer = closest.map(|hit| distance < hit.distance || (distance == hit.distance && id < hit.id)).unwrap_or(true);
+            if distance <= max_distance && closer {
+                closest = Some(SpriteHit {
+                    id,
+                    point       : (origin.0 + ray_dir.0 * distance, origin.1 + ray_dir.1 * distance),
+                    distance,
+                });
+            }
+        }
+
+        closest
+    }
+}
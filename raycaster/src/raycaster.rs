@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use std::time::{SystemTime, UNIX_EPOCH};
+use crate::ray::MAX_RAY_STEPS;
 
 #[cfg(not(target_arch = "wasm32"))]
 use rayon::{slice::ParallelSliceMut, iter::{IndexedParallelIterator, ParallelIterator}};
@@ -15,11 +16,36 @@ pub struct Raycaster {
     dir                     : vec2,
     plane                   : vec2,
 
-    anim_curr_time          : u128,
+    /// Length of the camera plane, defines the field of view
+    plane_length            : f32,
+
+    /// Keeps the pixels square for any aspect ratio of the render rect
+    aspect_correct          : bool,
+
+    /// Height of the camera above the floor in cells
+    pos_z                   : f32,
+
+    /// Shifts the horizon, in screen heights. Positive values look up.
+    pitch                   : f32,
+
+    /// How textures are sampled
+    filter                  : Filter,
+
+    /// Interpolates the light between the cells, otherwise every cell is lit evenly
+    smooth_lighting         : bool,
+
+    /// Milliseconds since the last animation frame
+    anim_curr_time          : f32,
     anim_time               : u128,
     anim_counter            : usize,
 }
 
+impl Default for Raycaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Raycaster {
 
     pub fn new() -> Self {
@@ -28,6 +54,14 @@ impl Raycaster {
             pos             : vec2::new(5.0, 5.0),
             dir             : vec2::new(-1.0, 0.0),
             plane           : vec2::new(0.0, 0.66),
+            plane_length    : 0.66,
+            aspect_correct  : false,
+
+            pos_z           : 0.5,
+            pitch           : 0.0,
+
+            filter          : Filter::Nearest,
+            smooth_lighting : true,
 
             time            : 0,
             old_time        : 0,
@@ -35,865 +69,462 @@ impl Raycaster {
             move_speed      : 0.0,
             rot_speed       : 0.0,
 
-            anim_curr_time  : 0,
+            anim_curr_time  : 0.0,
             anim_time       : 250,
             anim_counter    : 0,
         }
     }
 
     #[cfg(feature = "single_threaded")]
-    pub fn render(&mut self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &mut WorldMap) {
+    pub fn render(&self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &WorldMap) {
         self.render_st(frame, rect, stride, world);
     }
 
     #[cfg(not(feature = "single_threaded"))]
-    pub fn render(&mut self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &mut WorldMap) {
+    pub fn render(&self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &WorldMap) {
         self.render_mt(frame, rect, stride, world);
     }
 
-    /// Renders the world map into the frame inside the given rectangle
-    pub fn render_st(&mut self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &mut WorldMap) {
-
-        let start = self.get_time();
-
-        // Update animation counter every anim_time milliseconds
-        if self.anim_curr_time > self.anim_time {
-            self.anim_curr_time -= self.anim_time;
-            self.anim_counter = self.anim_counter.wrapping_add(1);
-            world.compute_lighting();
-        }
-
-        let width = rect.2 as i32;
-        let height = rect.3 as i32;
-
-        let pos = self.pos.clone();
-        let dir = self.dir.clone();
-        let plane = self.plane.clone();
-
-        // Sort the sprites
+    /// Renders the world map into a new RGBA buffer of the given size
+    pub fn render_to_buffer(&self, width: usize, height: usize, world: &WorldMap) -> Vec<u8> {
+        let mut buffer = vec![0; width * height * 4];
+        self.render(&mut buffer[..], (0, 0, width, height), width, world);
+        buffer
+    }
 
-        let mut sprites = world.sprites.clone();
+    /// Renders the world map into the frame inside the given rectangle
+    pub fn render_st(&self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &WorldMap) {
 
-        // Calculate sprite distances
+        let sprites = self.sort_sprites(world);
 
-        for sprite in &mut sprites {
-            sprite.distance = (pos.x - sprite.x) * (pos.x - sprite.x) + (pos.y - sprite.y) * (pos.y - sprite.y);
+        for x in 0..rect.2 {
+            let offset = ((rect.1 * stride) + rect.0 + x) * 4;
+            self.render_column(frame, offset, stride * 4, x as i32, rect.2 as i32, rect.3 as i32, world, &sprites);
         }
+    }
 
-        sprites.sort_by(|a,b| b.distance.partial_cmp( &a.distance ).unwrap() );
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Renders the world map into the frame inside the given rectangle
+    pub fn render_mt(&self, frame: &mut [u8], rect: (usize, usize, usize, usize), stride: usize, world: &WorldMap) {
 
-        //
+        let width = rect.2;
+        let height = rect.3;
 
-        let ceiling_tile = world.get_default_ceiling();
-        let mut ceiling_is_textured = false;
+        let sprites = self.sort_sprites(world);
 
-        let floor_tile = world.get_default_floor();
-        let mut floor_is_textured = false;
-
-        // Background color if no ceiling or floor tile is set
-        if ceiling_tile.is_none() || floor_tile.is_none() {
-            for y in rect.1..rect.3 {
-                for x in rect.0..rect.2 {
-                    frame[(y*stride+x)*4+0] = 0;
-                    frame[(y*stride+x)*4+1] = 0;
-                    frame[(y*stride+x)*4+2] = 0;
-                    frame[(y*stride+x)*4+3] = 255;
-                }
-            }
-        }
+        // The buffer stores the image 90 degrees rotated, i.e. column by column, so that every column is a continuous slice
+        let mut buffer = vec![0; width * height * 4];
 
-        // Ceiling color
-        if let Some(ceiling) = ceiling_tile {
-            if let Some(color) = ceiling.color {
-                for y in rect.1..rect.3 / 2 {
-                    for x in rect.0..rect.2 {
-                        let o = (y*stride+x)*4;
-                        frame[o..o+4].copy_from_slice(&color);
-                    }
-                }
-            } else {
-                ceiling_is_textured = true;
-            }
-        }
+        buffer
+            .par_chunks_exact_mut(height * 4)
+            .enumerate()
+            .for_each(|(x, column)| {
+                self.render_column(column, 0, 4, x as i32, width as i32, height as i32, world, &sprites);
+            });
 
-        // Floor color
-        if let Some(floor) = floor_tile {
-            if let Some(color) = floor.color {
-                for y in rect.1 + rect.3 / 2..rect.1 + rect.3 {
-                    for x in rect.0..rect.2 {
-                        let o = (y*stride+x)*4;
-                        frame[o..o+4].copy_from_slice(&color);
-                    }
+        // Copy the buffer into the frame 90 degrees rotated
+        frame
+            .par_chunks_exact_mut(stride * 4)
+            .enumerate()
+            .skip(rect.1)
+            .take(height)
+            .for_each(|(y, line)| {
+                let ry = y - rect.1;
+                for x in 0..width {
+                    let off = (rect.0 + x) * 4;
+                    let buffer_off = (x * height + ry) * 4;
+                    line[off..off+4].copy_from_slice(&buffer[buffer_off..buffer_off+4]);
                 }
-            } else {
-                floor_is_textured = true;
-            }
-        }
-
-        // Texture the ceiling and floor
-
-        if ceiling_is_textured || floor_is_textured {
-
-            for y in rect.1 + rect.3 / 2..rect.3 {
-
-                // rayDir for leftmost ray (x = 0) and rightmost ray (x = w)
-                let ray_dir_x0 = dir.x - plane.x;
-                let ray_dir_y0 = dir.y - plane.y;
-                let ray_dir_x1 = dir.x + plane.x;
-                let ray_dir_y1 = dir.y + plane.y;
-
-                // Current y position compared to the center of the screen (the horizon)
-                let p = y - rect.3 / 2;
-
-                // Vertical position of the camera.
-                let pos_z = 0.5 * rect.3 as f32;
-
-                // Horizontal distance from the camera to the floor for the current row.
-                // 0.5 is the z position exactly in the middle between floor and ceiling.
-                let row_distance = pos_z / p as f32;
+            });
+    }
 
-                // calculate the real world step vector we have to add for each x (parallel to camera plane)
-                // adding step by step avoids multiplications with a weight in the inner loop
-                let floor_step_x = row_distance * (ray_dir_x1 - ray_dir_x0) / rect.2 as f32;
-                let floor_step_y = row_distance * (ray_dir_y1 - ray_dir_y0) / rect.2 as f32;
+    /// Returns references to the sprites sorted back to front, sprites at the same distance are sorted by id
+    fn sort_sprites<'a>(&self, world: &'a WorldMap) -> Vec<&'a Sprite> {
+        let pos = self.pos;
 
-                // real world coordinates of the leftmost column. This will be updated as we step to the right.
-                let mut floor_x = pos.x + row_distance * ray_dir_x0;
-                let mut floor_y = pos.y + row_distance * ray_dir_y0;
+        // Calculate sprite distances
 
-                let mix_factor = row_distance / world.fog_distance;
+        let mut sprites : Vec<(f32, SpriteId, &Sprite)> = world.sprites().map(|(id, sprite)| {
+            ((pos.x - sprite.x) * (pos.x - sprite.x) + (pos.y - sprite.y) * (pos.y - sprite.y), id, sprite)
+        }).collect();
 
-                for x in rect.0..rect.2 {
+        sprites.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        sprites.into_iter().map(|(_, _, sprite)| sprite).collect()
+    }
 
-                    // the cell coord is simply got from the integer parts of floorX and floorY
-                    let cell_x = floor_x.floor();
-                    let cell_y = floor_y.floor();
+    /// Renders the screen column x of a width x height image, the pixel of row y is written at offset + y * pitch in the buffer.
+    /// This is the shared core of the single and the multi threaded renderer.
+    #[allow(clippy::too_many_arguments)]
+    fn render_column(&self, buffer: &mut [u8], offset: usize, pitch: usize, x: i32, width: i32, height: i32, world: &WorldMap, sprites: &[&Sprite]) {
 
-                    if let Some(floor) = floor_tile {
-                        if let Some((image_id, rect)) = floor.texture {
-                            let tex_x = ((rect.2 as f32 * (floor_x - cell_x)) as usize).clamp(0, rect.2 - 1);
-                            let tex_y = ((rect.3 as f32 * (floor_y - cell_y)) as usize).clamp(0, rect.3 - 1);
+        let scale = self.get_projection_scale(width, height);
+        let horizon = self.get_horizon(height);
 
-                            if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                                let tex_off = rect.0 + tex_x * 4 + rect.1 + ((tex_y as usize) * *tex_width as usize * 4);
-                                let off = x * 4 + y * 4 * stride;
+        let pos = self.pos;
+        let dir = self.dir;
+        let plane = self.plane;
 
-                                let mut floor_color : [u8;4] = [0, 0, 0, 0];
-                                floor_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                let color = self.add_lighting(&mut floor_color, &world, (floor_x as i32, floor_y as i32), mix_factor);
-                                frame[off..off+4].copy_from_slice(&color);
-                            }
-                        }
-                    }
-
-                    if let Some(ceiling) = ceiling_tile {
-                        if let Some((image_id, tex_rect)) = ceiling.texture {
-                            let tex_x = ((tex_rect.2 as f32 * (floor_x - cell_x)) as usize).clamp(0, tex_rect.2 - 1);
-                            let tex_y = ((tex_rect.3 as f32 * (floor_y - cell_y)) as usize).clamp(0, tex_rect.3 - 1);
+        let ceiling_tile = world.get_default_ceiling();
+        let floor_tile = world.get_default_floor();
 
-                            if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                                let tex_off = tex_rect.0 + tex_x * 4 + tex_rect.1 + ((tex_y as usize) * *tex_width as usize * 4);
-                                let off = x * 4 + (rect.3 - y - 1) * 4 * stride;
+        // Ceiling and floor colors, black if no ceiling or floor tile is set
+        let ceiling_color = ceiling_tile.and_then(|tile| tile.color).unwrap_or([0, 0, 0, 255]);
+        let floor_color = floor_tile.and_then(|tile| tile.color).unwrap_or([0, 0, 0, 255]);
 
-                                let mut ceiling_color : [u8;4] = [0, 0, 0, 0];
-                                ceiling_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                let color = self.add_lighting(&mut ceiling_color, &world, (floor_x as i32, floor_y as i32), mix_factor);
-                                frame[off..off+4].copy_from_slice(&color);
-                            }
-                        }
-                    }
-
-                    floor_x += floor_step_x;
-                    floor_y += floor_step_y;
-                }
-            }
+        for y in 0..height {
+            let o = offset + y as usize * pitch;
+            buffer[o..o+4].copy_from_slice(if y < horizon { &ceiling_color } else { &floor_color });
         }
 
-        // Render the walls
-
-        let mut z_buffer = vec![f32::MAX; rect.2 as usize];
-
-        for x in rect.0..rect.2 {
+        // Texture the ceiling and floor, draw the floor and ceiling cells
 
-            let camera_x = 2.0 * x as f32 / width as f32 - 1.0; //x-coordinate in camera space
-            let ray_dir_x = dir.x + plane.x * camera_x;
-            let ray_dir_y = dir.y + plane.y * camera_x;
+        // rayDir for leftmost ray (x = 0) and rightmost ray (x = w)
+        let ray_dir_x0 = dir.x - plane.x;
+        let ray_dir_y0 = dir.y - plane.y;
+        let ray_dir_x1 = dir.x + plane.x;
+        let ray_dir_y1 = dir.y + plane.y;
 
-            // which box of the map we're in
-            let mut map_x = pos.x as i32;
-            let mut map_y = pos.y as i32;
-
-            // length of ray from current position to next x or y-side
-            let mut side_dist_x;
-            let mut side_dist_y;
-
-            // length of ray from one x or y-side to next x or y-side
-            let delta_dist_x = if ray_dir_x == 0.0 { f32::MAX } else { (1.0 / ray_dir_x).abs() };
-            let delta_dist_y = if ray_dir_y == 0.0 { f32::MAX } else { (1.0 / ray_dir_y).abs() };
-            let perp_wall_dist;
-
-            // what direction to step in x or y-direction (either +1 or -1)
-            let step_x;
-            let step_y;
-
-            let mut hit = false; //was there a wall hit?
-            let mut side = 0; //was a NS or a EW wall hit?
-
-            // calculate step and initial sideDist
-            if ray_dir_x < 0.0 {
-                step_x = -1;
-                side_dist_x = (pos.x - map_x as f32) * delta_dist_x;
-            } else {
-                step_x = 1;
-                side_dist_x = (map_x as f32 + 1.0 - pos.x) * delta_dist_x;
-            }
+        let has_decals = world.has_decals();
 
-            if ray_dir_y < 0.0 {
-                step_y = -1;
-                side_dist_y = (pos.y - map_y as f32) * delta_dist_y;
-            } else {
-                step_y = 1;
-                side_dist_y = (map_y as f32 + 1.0 - pos.y) * delta_dist_y;
-            }
+        for y in 0..height {
 
-            // perform DDA
-            for _ in 0..40 {
-                // jump to next map square, either in x-direction, or in y-direction
-                if side_dist_x < side_dist_y {
-                    side_dist_x += delta_dist_x;
-                    map_x += step_x;
-                    side = 0;
-                } else {
-                    side_dist_y += delta_dist_y;
-                    map_y += step_y;
-                    side = 1;
-                }
+            let is_floor = y > horizon;
 
-                // check if ray has hit a wall
-                if world.has_wall(map_x, map_y) == true {
-                    hit = true;
-                    break;
-                }
+            // Current y position compared to the horizon
+            let p = (y - horizon).abs();
+            if p == 0 {
+                continue;
             }
 
-            if hit {
+            // Vertical position of the camera above the floor or below the ceiling.
+            let pos_z = (if is_floor { self.pos_z } else { 1.0 - self.pos_z }) * scale;
 
-                // calculate distance projected on camera direction (Euclidean distance would give fisheye effect!)
-                if side == 0 {
-                    perp_wall_dist = side_dist_x - delta_dist_x;
-                } else {
-                    perp_wall_dist = side_dist_y - delta_dist_y;
-                }
+            // Horizontal distance from the camera to the floor or ceiling for the current row.
+            let row_distance = pos_z / p as f32;
 
-                // calculate height of line to draw on screen
-                let line_height = (height as f32 / perp_wall_dist) as i32;
+            // calculate the real world step vector we have to add for each x (parallel to camera plane)
+            let floor_step_x = row_distance * (ray_dir_x1 - ray_dir_x0) / width as f32;
+            let floor_step_y = row_distance * (ray_dir_y1 - ray_dir_y0) / width as f32;
 
-                // calculate lowest and highest pixel to fill in current stripe
-                let mut draw_start = -line_height / 2 + height / 2;
-                if draw_start < 0 {
-                    draw_start = 0;
-                }
-
-                let mut draw_end = line_height / 2 + height / 2;
-                if draw_end >= height {
-                    draw_end = height - 1;
-                }
-
-                let mix_factor = perp_wall_dist / world.fog_distance;
-
-                if let Some(tile) = world.get_wall(map_x, map_y) {
-
-                    if let Some((image_id, rect)) = self.get_texture(&tile) {
-
-                        // texturing calculations
-
-                        // calculate value of wall_x
-                        let mut wall_x; //where exactly the wall was hit
-                        if side == 0 {
-                            wall_x = pos.y + perp_wall_dist * ray_dir_y;
-                        } else {
-                            wall_x = pos.x + perp_wall_dist * ray_dir_x;
-                        }
-                        wall_x -= wall_x.floor();
-
-                        // x coordinate on the texture
-                        let mut tex_x = (wall_x * rect.2 as f32) as usize;
-                        if side == 0 && ray_dir_x > 0.0 {
-                            tex_x = rect.2 - tex_x - 1;
-                        }
-                        if side == 1 && ray_dir_y < 0.0 {
-                            tex_x = rect.2 - tex_x - 1;
-                        }
+            // real world coordinates of the current column
+            let floor_x = pos.x + row_distance * ray_dir_x0 + floor_step_x * x as f32;
+            let floor_y = pos.y + row_distance * ray_dir_y0 + floor_step_y * x as f32;
 
-                        // How much to increase the texture coordinate per screen pixel
-                        let step = 1.0 * rect.3 as f32 / line_height as f32;
+            let mix_factor = row_distance / world.fog_distance;
 
-                        let mut tex_pos = (draw_start - height / 2 + line_height / 2) as f32 * step;
+            // the cell coord is simply got from the integer parts of floorX and floorY
+            let cell_x = floor_x.floor();
+            let cell_y = floor_y.floor();
 
-                        if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                            let off_x = x * 4;
-                            for y in draw_start..draw_end {
-                                let tex_off = rect.0 + tex_x * 4 + rect.1 + ((tex_pos as usize) * *tex_width as usize * 4);
-                                let off = off_x + y as usize * 4 * stride;
+            let map_x = cell_x as i32;
+            let map_y = cell_y as i32;
 
-                                let mut wall_color : [u8;4] = [0, 0, 0, 0];
-                                wall_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                let color = self.add_lighting(&mut wall_color, &world, (map_x, map_y), mix_factor);
-                                frame[off..off+4].copy_from_slice(&color);
+            let (cell_tile, default_tile) = if is_floor {
+                (world.get_floor(map_x, map_y), floor_tile)
+            } else if world.is_ceiling_open(map_x, map_y) {
+                (None, None)
+            } else {
+                (world.get_ceiling(map_x, map_y), ceiling_tile)
+            };
+
+            if let Some(tile) = cell_tile.or(default_tile) {
+                let off = offset + y as usize * pitch;
+                if let Some((image_id, tex_rect)) = self.get_texture(tile) {
+                    let tex_x = tex_rect.2 as f32 * (floor_x - cell_x);
+                    let mut tex_y = tex_rect.3 as f32 * (floor_y - cell_y);
+                    if is_floor {
+                        tex_y = tex_rect.3 as f32 - tex_y;
+                    }
 
-                                tex_pos += step;
-                            }
-                        }
+                    if let Some(tile_color) = self.sample_texture(world, image_id, tex_rect, tex_x, tex_y, tex_rect.2 as f32 * row_distance / scale) {
+                        let color = self.add_lighting(&tile_color, world, (floor_x, floor_y), mix_factor);
+                        buffer[off..off+4].copy_from_slice(&color);
                     }
+                } else if let Some(color) = tile.color {
+                    let color = self.add_lighting(&color, world, (floor_x, floor_y), mix_factor);
+                    buffer[off..off+4].copy_from_slice(&color);
                 }
-
-                // perpendicular distance is stored in the z-buffer for sprite casting
-                z_buffer[x] = perp_wall_dist;
-
-                /* color
-                let off_x = x * 4;
-
-                for y in draw_start..draw_end {
-                    let off = off_x + y as usize * 4 * self.width;
-                    frame[off] = 255;
-                    frame[off + 1] = 255;
-                    frame[off + 2] = 255;
-                    frame[off + 3] = 255;
+            } else if !is_floor {
+                if let Some(sky) = world.get_sky() {
+                    let off = offset + y as usize * pitch;
+                    let color = self.get_sky_color(world, sky, x, width, y, horizon, scale);
+                    buffer[off..off+4].copy_from_slice(&color);
                 }
-                */
             }
-        }
-
-        // Render the sprites
-
-        for sprite in &sprites {
-
-            // translate sprite position to relative to camera
-            let sprite_x = sprite.x - pos.x;
-            let sprite_y = sprite.y - pos.y;
-
-            // transform sprite with the inverse camera matrix
-            // [ planeX   dirX ] -1                                       [ dirY      -dirX ]
-            // [               ]       =  1/(planeX*dirY-dirX*planeY) *   [                 ]
-            // [ planeY   dirY ]                                          [ -planeY  planeX ]
-
-            let inv_det = 1.0 / (plane.x * dir.y - dir.x * plane.y); //required for correct matrix multiplication
 
-            let transform_x = inv_det * (dir.y * sprite_x - dir.x * sprite_y);
-            let transform_y = inv_det * (-plane.y * sprite_x + plane.x * sprite_y); //this is actually the depth inside the screen, that what Z is in 3D
-
-            let v_move_screen = (sprite.move_y / transform_y) as i32;
-
-            let mix_factor = transform_y / world.fog_distance;
-
-            let sprite_screen_x = ((width as f32 / 2.0) * (1.0 + transform_x / transform_y)) as i32;
-
-            // calculate height of the sprite on screen
-            let sprite_height = ((height as f32 / (transform_y)) as i32).abs() / sprite.shrink; //using 'transformY' instead of the real distance prevents fisheye
-            // calculate lowest and highest pixel to fill in current stripe
-            let mut draw_start_y = -sprite_height / 2 + height / 2 + v_move_screen;
-            if draw_start_y < 0 { draw_start_y = 0; }
-            let mut draw_end_y = sprite_height / 2 + height / 2 + v_move_screen;
-            if draw_end_y >= height { draw_end_y = height - 1; }
-
-            // calculate width of the sprite
-            let sprite_width = ((height as f32 / (transform_y)) as i32).abs() / sprite.shrink;
-            let mut draw_start_x = -sprite_width / 2 + sprite_screen_x;
-            if draw_start_x < 0 { draw_start_x = 0; }
-            let mut draw_end_x = sprite_width / 2 + sprite_screen_x;
-            if draw_end_x >= width { draw_end_x = width - 1; }
-
-            if let Some((image_id, tex_rect)) = self.get_texture(&sprite.tile) {
-                if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-
-                    // loop through every vertical stripe of the sprite on screen
-                    for stripe in draw_start_x..draw_end_x {
-                        let tex_x = ((256 * (stripe - (-sprite_width / 2 + sprite_screen_x)) * tex_rect.2 as i32 / sprite_width) / 256) as usize;
-
-                        // the conditions in the if are:
-                        // 1) it's in front of camera plane so you don't see things behind you
-                        // 2) it's on the screen (left)
-                        // 3) it's on the screen (right)
-                        // 4) ZBuffer, with perpendicular distance
-
-                        if transform_y > 0.0 && stripe > 0 && stripe < width && transform_y < z_buffer[stripe as usize] {
-                            for y in draw_start_y as usize .. draw_end_y as usize {
-
-                                let d = (y - v_move_screen as usize) * 256 - height as usize * 128 + sprite_height as usize * 128; //256 and 128 factors to avoid floats
-                                let tex_y = (((d * tex_rect.3) / sprite_height as usize) / 256) as usize;
-
-                                let tex_off = tex_rect.0 + tex_x * 4 + tex_rect.1 + ((tex_y as usize) * *tex_width as usize * 4);
-                                let off = (rect.0 + stripe as usize) * 4 + (rect.1 + y as usize) * 4 * stride;
-
-                                let mut sprite_color : [u8;4] = [0, 0, 0, 0];
-                                sprite_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                let tex_alpha = tex_data[tex_off+3] as f32 / 255.0;
-                                if tex_alpha > 0.0 {
-                                    let color = self.add_lighting(&mut sprite_color, &world, (sprite.x as i32, sprite.y as i32), mix_factor);
-                                    frame[off..off+4].copy_from_slice(&color);
-                                }
-                            }
-                        }
+            if is_floor && has_decals {
+                // draw the floor decals of the cell on top of the floor
+                let off = offset + y as usize * pitch;
+                for decal in world.get_decals(map_x, map_y) {
+                    if decal.surface != Surface::Floor {
+                        continue;
+                    }
+                    if let Some((u, v)) = decal.get_uv(floor_x - cell_x, floor_y - cell_y) {
+                        self.draw_decal_pixel(&mut buffer[off..off+4], world, decal, u, 1.0 - v, row_distance / scale / decal.rect.2, (floor_x, floor_y), mix_factor);
                     }
                 }
             }
         }
 
-        let stop = self.get_time();
-        println!("tick time {:?}", stop - start);
-
-        self.old_time = self.time;
-        self.time = self.get_time();
-
-        if self.old_time > 0 {
-            self.anim_curr_time += self.time - self.old_time;
-        }
-
-        let frame_time = (self.time - self.old_time) as f32 / 1000.0;
-        // println!("fps {}", 1.0 / frame_time); //FPS counter
+        // Render the walls
 
-        self.move_speed = frame_time * 5.0; //the constant value is in squares/second
-        self.rot_speed = frame_time * 2.0;
-    }
+        // the z-buffer stores the perpendicular distance of the walls per pixel for sprite casting
+        let mut z_buffer = vec![f32::MAX; height as usize];
 
-    #[cfg(not(target_arch = "wasm32"))]
-    /// Renders the world map into the frame inside the given rectangle
-    pub fn render_mt(&mut self, frame: &mut [u8], in_rect: (usize, usize, usize, usize), in_stride: usize, world: &mut WorldMap) {
+        // transparent walls hit by the ray of this column
+        let mut transparent_walls = vec![];
 
-        let rect = (0, 0, in_rect.2, in_rect.3);
-        let stride = rect.3;
+        let camera_x = 2.0 * x as f32 / width as f32 - 1.0; //x-coordinate in camera space
+        let ray_dir_x = dir.x + plane.x * camera_x;
+        let ray_dir_y = dir.y + plane.y * camera_x;
 
-        //let start = self.get_time();
+        // which box of the map we're in
+        let mut map_x = pos.x as i32;
+        let mut map_y = pos.y as i32;
 
-        // Update animation counter every anim_time milliseconds
-        if self.anim_curr_time > self.anim_time {
-            self.anim_curr_time -= self.anim_time;
-            self.anim_counter = self.anim_counter.wrapping_add(1);
-            world.compute_lighting();
-        }
+        // length of ray from current position to next x or y-side
+        let mut side_dist_x;
+        let mut side_dist_y;
 
-        let width = rect.2 as i32;
-        let height = rect.3 as i32;
+        // length of ray from one x or y-side to next x or y-side
+        let delta_dist_x = if ray_dir_x == 0.0 { f32::MAX } else { (1.0 / ray_dir_x).abs() };
+        let delta_dist_y = if ray_dir_y == 0.0 { f32::MAX } else { (1.0 / ray_dir_y).abs() };
 
-        let pos = self.pos.clone();
-        let dir = self.dir.clone();
-        let plane = self.plane.clone();
+        // what direction to step in x or y-direction (either +1 or -1)
+        let step_x;
+        let step_y;
 
-        let mut buffer = vec![0; rect.2 * rect.3 * 4];
+        let mut side; //was a NS or a EW wall hit?
 
-        // Sort the sprites
+        // everything above clip_y is still free, walls further away are only drawn there
+        let mut clip_y = height;
 
-        let mut sprites = world.sprites.clone();
-
-        // Calculate sprite distances
+        // calculate step and initial sideDist
+        if ray_dir_x < 0.0 {
+            step_x = -1;
+            side_dist_x = (pos.x - map_x as f32) * delta_dist_x;
+        } else {
+            step_x = 1;
+            side_dist_x = (map_x as f32 + 1.0 - pos.x) * delta_dist_x;
+        }
 
-        for sprite in &mut sprites {
-            sprite.distance = (pos.x - sprite.x) * (pos.x - sprite.x) + (pos.y - sprite.y) * (pos.y - sprite.y);
+        if ray_dir_y < 0.0 {
+            step_y = -1;
+            side_dist_y = (pos.y - map_y as f32) * delta_dist_y;
+        } else {
+            step_y = 1;
+            side_dist_y = (map_y as f32 + 1.0 - pos.y) * delta_dist_y;
         }
 
-        sprites.sort_by(|a,b| b.distance.partial_cmp( &a.distance ).unwrap() );
+        // perform DDA
+        for _ in 0..MAX_RAY_STEPS {
+            // the cell the ray comes from, walls and doors are lit from the side they are seen from
+            let prev_cell = (map_x, map_y);
 
-        // -- Render per line
+            // jump to next map square, either in x-direction, or in y-direction
+            if side_dist_x < side_dist_y {
+                side_dist_x += delta_dist_x;
+                map_x += step_x;
+                side = 0;
+            } else {
+                side_dist_y += delta_dist_y;
+                map_y += step_y;
+                side = 1;
+            }
 
-        buffer
-            .par_rchunks_exact_mut(height as usize * 4)
-            .enumerate()
-            .for_each(|(x, line)| {
+            let mut hit = None; //was there a wall or door hit?
 
-            let ceiling_tile = world.get_default_ceiling();
-            let floor_tile = world.get_default_floor();
+            // check if ray has hit a wall
+            if let Some(wall) = world.get_wall_cell(map_x, map_y) {
 
-            let mut z_buffer = f32::MAX;
+                // calculate distance projected on camera direction (Euclidean distance would give fisheye effect!)
+                let perp_wall_dist;
+                let mut wall_x; //where exactly the wall was hit
+                if side == 0 {
+                    perp_wall_dist = side_dist_x - delta_dist_x;
+                    wall_x = pos.y + perp_wall_dist * ray_dir_y;
+                } else {
+                    perp_wall_dist = side_dist_y - delta_dist_y;
+                    wall_x = pos.x + perp_wall_dist * ray_dir_x;
+                }
+                wall_x -= wall_x.floor();
 
-            // Ceiling color
-            if let Some(ceiling) = ceiling_tile {
-                if let Some(color) = ceiling.color {
-                    for y in 0.. rect.3 / 2 {
-                        let o = y*4;
-                        line[o..o+4].copy_from_slice(&color);
-                    }
+                if (side == 0 && ray_dir_x > 0.0) || (side == 1 && ray_dir_y < 0.0) {
+                    wall_x = 1.0 - wall_x;
                 }
-            }
 
+                let face = Face::from_ray(side, step_x, step_y);
+                let decals = if has_decals { Some((face, world.get_decals(map_x, map_y))) } else { None };
 
-            // Floor color
-            if let Some(floor) = floor_tile {
-                if let Some(color) = floor.color {
-                    for y in rect.3 / 2..rect.3 {
-                        let o = y*4;
-                        line[o..o+4].copy_from_slice(&color);
-                    }
+                hit = Some((wall.get_face(face), perp_wall_dist, wall_x, wall.height, wall.transparent, decals));
+            } else if let Some(door) = world.get_door(map_x, map_y) {
+                // check if ray has hit the closed part of a door
+                if let Some((door_dist, door_x)) = door.hit_ray((map_x, map_y), (pos.x, pos.y), (ray_dir_x, ray_dir_y), (side_dist_x, side_dist_y), (delta_dist_x, delta_dist_y), side) {
+                    side = if door.vertical { 0 } else { 1 };
+                    hit = Some((&door.tile, door_dist, door_x, 1.0, false, None));
                 }
             }
 
-            // Texture the ceiling and floor
+            if let Some((tile, perp_wall_dist, wall_x, wall_height, transparent, decals)) = hit {
 
-            for y in rect.3 /2..rect.3 {
+                // the hit position for the lighting, moved into the cell in front of the wall so that the light behind the wall does not reach this face
+                let light_pos = (
+                    (pos.x + perp_wall_dist * ray_dir_x).clamp(prev_cell.0 as f32, prev_cell.0 as f32 + 0.999),
+                    (pos.y + perp_wall_dist * ray_dir_y).clamp(prev_cell.1 as f32, prev_cell.1 as f32 + 0.999));
 
-                // rayDir for leftmost ray (x = 0) and rightmost ray (x = w)
-                let ray_dir_x0 = dir.x - plane.x;
-                let ray_dir_y0 = dir.y - plane.y;
-                let ray_dir_x1 = dir.x + plane.x;
-                let ray_dir_y1 = dir.y + plane.y;
-
-                // Current y position compared to the center of the screen (the horizon)
-                let p = y - rect.3 / 2;
-
-                // Vertical position of the camera.
-                let pos_z = 0.5 * rect.3 as f32;
-
-                // Horizontal distance from the camera to the floor for the current row.
-                // 0.5 is the z position exactly in the middle between floor and ceiling.
-                let row_distance = pos_z / p as f32;
-
-                // calculate the real world step vector we have to add for each x (parallel to camera plane)
-                // adding step by step avoids multiplications with a weight in the inner loop
-                let floor_step_x = row_distance * (ray_dir_x1 - ray_dir_x0) / rect.2 as f32;
-                let floor_step_y = row_distance * (ray_dir_y1 - ray_dir_y0) / rect.2 as f32;
-
-                // real world coordinates of the leftmost column. This will be updated as we step to the right.
-                let mut floor_x = pos.x + row_distance * ray_dir_x0;
-                let mut floor_y = pos.y + row_distance * ray_dir_y0;
-
-                floor_x += floor_step_x * x as f32;
-                floor_y += floor_step_y * x as f32;
-
-                let mix_factor = row_distance / world.fog_distance;
-
-                // the cell coord is simply got from the integer parts of floorX and floorY
-                let cell_x = floor_x.floor();
-                let cell_y = floor_y.floor();
-
-                let map_x = cell_x as i32;
-                let map_y = cell_y as i32;
-
-                let mut floor = world.get_floor(map_x, map_y);
-                if floor.is_none() {
-                    floor = floor_tile;
+                // transparent walls are drawn on top of the cells behind them after the DDA
+                if transparent {
+                    transparent_walls.push((tile, light_pos, side, wall_x, perp_wall_dist, wall_height, clip_y, decals));
+                    continue;
                 }
 
-                if let Some(floor) = floor {
-                    if let Some((image_id, rect)) = floor.texture {
-                        let tex_x = ((rect.2 as f32 * (floor_x - cell_x)) as usize).clamp(0, rect.2 - 1);
-                        let tex_y = ((rect.3 as f32 * (floor_y - cell_y)) as usize).clamp(0, rect.3 - 1);
-
-                        if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                            let tex_off = rect.0 + tex_x * 4 + rect.1 + ((rect.3 - tex_y as usize - 1) * *tex_width as usize * 4);
-                            let off = y * 4;
-
-                            let mut floor_color : [u8;4] = [0, 0, 0, 0];
-                            floor_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                            let color = self.add_lighting(&mut floor_color, &world, (floor_x as i32, floor_y as i32), mix_factor);
-
-                            line[off..off+4].copy_from_slice(&color);
-                        }
-                    }
+                let top = self.draw_wall_slice(buffer, offset, pitch, &mut z_buffer, world, tile, light_pos, side, wall_x, perp_wall_dist, wall_height, height, scale, clip_y, false);
+                if let Some((face, decals)) = decals {
+                    self.draw_wall_decals(buffer, offset, pitch, world, face, decals, light_pos, wall_x, perp_wall_dist, wall_height, height, scale, clip_y);
                 }
+                clip_y = clip_y.min(top);
 
-                if let Some(ceiling) = ceiling_tile {
-                    if let Some((image_id, tex_rect)) = ceiling.texture {
-                        let tex_x = ((tex_rect.2 as f32 * (floor_x - cell_x)) as usize).clamp(0, tex_rect.2 - 1);
-                        let tex_y = ((tex_rect.3 as f32 * (floor_y - cell_y)) as usize).clamp(0, tex_rect.3 - 1);
-
-                        if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                            let tex_off = tex_rect.0 + tex_x * 4 + tex_rect.1 + ((tex_y as usize) * *tex_width as usize * 4);
-                            let off = (rect.3 - y  - 1) * 4;
-
-                            let mut ceiling_color : [u8;4] = [0, 0, 0, 0];
-                            ceiling_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                            let color = self.add_lighting(&mut ceiling_color, &world, (floor_x as i32, floor_y as i32), mix_factor);
-                            line[off..off+4].copy_from_slice(&color);
-                        }
-                    }
+                // stop if even the tallest wall behind this one would be hidden
+                let line_height = scale / perp_wall_dist;
+                let max_top = horizon - ((world.get_max_wall_height() - self.pos_z) * line_height) as i32;
+                if max_top >= clip_y {
+                    break;
                 }
             }
+        }
 
-            let camera_x = 2.0 * x as f32 / width as f32 - 1.0; //x-coordinate in camera space
-            let ray_dir_x = dir.x + plane.x * camera_x;
-            let ray_dir_y = dir.y + plane.y * camera_x;
-
-            // which box of the map we're in
-            let mut map_x = pos.x as i32;
-            let mut map_y = pos.y as i32;
-
-            // length of ray from current position to next x or y-side
-            let mut side_dist_x;
-            let mut side_dist_y;
-
-            // length of ray from one x or y-side to next x or y-side
-            let delta_dist_x = if ray_dir_x == 0.0 { f32::MAX } else { (1.0 / ray_dir_x).abs() };
-            let delta_dist_y = if ray_dir_y == 0.0 { f32::MAX } else { (1.0 / ray_dir_y).abs() };
-            let perp_wall_dist;
-
-            // what direction to step in x or y-direction (either +1 or -1)
-            let step_x;
-            let step_y;
-
-            let mut hit = false; //was there a wall hit?
-            let mut side = 0; //was a NS or a EW wall hit?
-
-            // calculate step and initial sideDist
-            if ray_dir_x < 0.0 {
-                step_x = -1;
-                side_dist_x = (pos.x - map_x as f32) * delta_dist_x;
-            } else {
-                step_x = 1;
-                side_dist_x = (map_x as f32 + 1.0 - pos.x) * delta_dist_x;
+        // draw the transparent walls back to front
+        for (tile, light_pos, side, wall_x, dist, wall_height, clip_y, decals) in transparent_walls.drain(..).rev() {
+            self.draw_wall_slice(buffer, offset, pitch, &mut z_buffer, world, tile, light_pos, side, wall_x, dist, wall_height, height, scale, clip_y, true);
+            if let Some((face, decals)) = decals {
+                self.draw_wall_decals(buffer, offset, pitch, world, face, decals, light_pos, wall_x, dist, wall_height, height, scale, clip_y);
             }
+        }
 
-            if ray_dir_y < 0.0 {
-                step_y = -1;
-                side_dist_y = (pos.y - map_y as f32) * delta_dist_y;
-            } else {
-                step_y = 1;
-                side_dist_y = (map_y as f32 + 1.0 - pos.y) * delta_dist_y;
-            }
+        // Render the sprites
 
-            // perform DDA
-            for _ in 0..40 {
-                // jump to next map square, either in x-direction, or in y-direction
-                if side_dist_x < side_dist_y {
-                    side_dist_x += delta_dist_x;
-                    map_x += step_x;
-                    side = 0;
-                } else {
-                    side_dist_y += delta_dist_y;
-                    map_y += step_y;
-                    side = 1;
-                }
+        for sprite in sprites {
 
-                // check if ray has hit a wall
-                if world.has_wall(map_x, map_y) == true {
-                    hit = true;
-                    break;
-                }
+            // the conditions are:
+            // 1) it's in front of camera plane so you don't see things behind you
+            // 2) the column is inside the vertical stripes of the sprite on screen
+            let Some((transform_y, sprite_left, sprite_width, sprite_top, sprite_height)) = self.project_sprite(sprite, width, height) else { continue };
+            if (x as f32) < sprite_left.floor() || (x as f32) >= (sprite_left + sprite_width).floor() || x >= width - 1 {
+                continue;
             }
 
-            if hit {
+            let draw_start_y = (sprite_top as i32).max(0);
+            let draw_end_y = ((sprite_top + sprite_height) as i32).min(height - 1);
 
-                // calculate distance projected on camera direction (Euclidean distance would give fisheye effect!)
-                if side == 0 {
-                    perp_wall_dist = side_dist_x - delta_dist_x;
-                } else {
-                    perp_wall_dist = side_dist_y - delta_dist_y;
-                }
+            let mix_factor = transform_y / world.fog_distance;
 
-                // calculate height of line to draw on screen
-                let line_height = (height as f32 / perp_wall_dist) as i32;
+            let opacity = sprite.opacity.clamp(0.0, 1.0);
+            if opacity <= 0.0 {
+                continue;
+            }
 
-                // calculate lowest and highest pixel to fill in current stripe
-                let mut draw_start = -line_height / 2 + height / 2;
-                if draw_start < 0 {
-                    draw_start = 0;
-                }
+            if let Some((image_id, tex_rect)) = self.get_sprite_texture(sprite) {
 
-                let mut draw_end = line_height / 2 + height / 2;
-                if draw_end >= height {
-                    draw_end = height - 1;
-                }
+                let tex_x = (x as f32 - sprite_left) * tex_rect.2 as f32 / sprite_width;
+                let texel_size = tex_rect.2 as f32 / sprite_width;
 
-                let mix_factor = perp_wall_dist / world.fog_distance;
+                for (y, z) in z_buffer.iter().enumerate().take(draw_end_y.max(0) as usize).skip(draw_start_y as usize) {
 
-                if let Some(tile) = world.get_wall(map_x, map_y) {
+                    // ZBuffer, with perpendicular distance
+                    if transform_y >= *z {
+                        continue;
+                    }
 
-                    if let Some((image_id, rect)) = self.get_texture(&tile) {
+                    let tex_y = ((y as f32 - sprite_top) * tex_rect.3 as f32 / sprite_height).clamp(0.0, tex_rect.3 as f32 - 0.5);
 
-                        // texturing calculations
+                    let off = offset + y * pitch;
 
-                        // calculate value of wall_x
-                        let mut wall_x; //where exactly the wall was hit
-                        if side == 0 {
-                            wall_x = pos.y + perp_wall_dist * ray_dir_y;
-                        } else {
-                            wall_x = pos.x + perp_wall_dist * ray_dir_x;
+                    if let Some(sprite_color) = self.sample_texture(world, image_id, tex_rect, tex_x, tex_y, texel_size) {
+                        let alpha = sprite_color[3] as f32 / 255.0 * opacity;
+                        if alpha <= 0.0 {
+                            continue;
                         }
-                        wall_x -= wall_x.floor();
 
-                        // x coordinate on the texture
-                        let mut tex_x = (wall_x * rect.2 as f32) as usize;
-                        if side == 0 && ray_dir_x > 0.0 {
-                            tex_x = rect.2 - tex_x - 1;
-                        }
-                        if side == 1 && ray_dir_y < 0.0 {
-                            tex_x = rect.2 - tex_x - 1;
-                        }
-
-                        // How much to increase the texture coordinate per screen pixel
-                        let step = 1.0 * rect.3 as f32 / line_height as f32;
-
-                        let mut tex_pos = (draw_start - height / 2 + line_height / 2) as f32 * step;
-
-                        if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-                            for y in draw_start..draw_end {
-                                let tex_off = rect.0 + tex_x * 4 + rect.1 + ((tex_pos as usize) * *tex_width as usize * 4);
-                                let off = y as usize * 4;
-
-                                let mut wall_color : [u8;4] = [0, 0, 0, 0];
-                                wall_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                let color = self.add_lighting(&mut wall_color, &world, (map_x, map_y), mix_factor);
-                                line[off..off+4].copy_from_slice(&color);
-
-                                tex_pos += step;
-                            }
+                        // the sprites are drawn back to front, translucent texels are blended over what is already drawn
+                        let mut color = self.add_lighting(&sprite_color, world, (sprite.x, sprite.y), mix_factor);
+                        if alpha < 1.0 {
+                            let mut background : [u8;4] = [0, 0, 0, 0];
+                            background.copy_from_slice(&buffer[off..off+4]);
+                            color = self.mix_color(&background, &color, alpha);
                         }
+                        buffer[off..off+4].copy_from_slice(&color);
                     }
                 }
-
-                // perpendicular distance is stored in the z-buffer for sprite casting
-                z_buffer = perp_wall_dist;
-
-                /* color
-                let off_x = x * 4;
-
-                for y in draw_start..draw_end {
-                    let off = off_x + y as usize * 4 * self.width;
-                    frame[off] = 255;
-                    frame[off + 1] = 255;
-                    frame[off + 2] = 255;
-                    frame[off + 3] = 255;
-                }
-                */
             }
+        }
+    }
 
-            // Render the sprites
-
-            for sprite in &sprites {
-
-                // translate sprite position to relative to camera
-                let sprite_x = sprite.x - pos.x;
-                let sprite_y = sprite.y - pos.y;
-
-                // transform sprite with the inverse camera matrix
-                // [ planeX   dirX ] -1                                       [ dirY      -dirX ]
-                // [               ]       =  1/(planeX*dirY-dirX*planeY) *   [                 ]
-                // [ planeY   dirY ]                                          [ -planeY  planeX ]
-
-                let inv_det = 1.0 / (plane.x * dir.y - dir.x * plane.y); //required for correct matrix multiplication
-
-                let transform_x = inv_det * (dir.y * sprite_x - dir.x * sprite_y);
-                let transform_y = inv_det * (-plane.y * sprite_x + plane.x * sprite_y); //this is actually the depth inside the screen, that what Z is in 3D
-
-                let v_move_screen = (sprite.move_y / transform_y) as i32;
-
-                let mix_factor = transform_y / world.fog_distance;
-
-                let sprite_screen_x = ((width as f32 / 2.0) * (1.0 + transform_x / transform_y)) as i32;
-
-                // calculate height of the sprite on screen
-                let sprite_height = ((height as f32 / (transform_y)) as i32).abs() / sprite.shrink; //using 'transformY' instead of the real distance prevents fisheye
-                // calculate lowest and highest pixel to fill in current stripe
-                let mut draw_start_y = -sprite_height / 2 + height / 2 + v_move_screen;
-                if draw_start_y < 0 { draw_start_y = 0; }
-                let mut draw_end_y = sprite_height / 2 + height / 2 + v_move_screen;
-                if draw_end_y >= height { draw_end_y = height - 1; }
-
-                // calculate width of the sprite
-                let sprite_width = ((height as f32 / (transform_y)) as i32).abs() / sprite.shrink;
-                let mut draw_start_x = -sprite_width / 2 + sprite_screen_x;
-                if draw_start_x < 0 { draw_start_x = 0; }
-                let mut draw_end_x = sprite_width / 2 + sprite_screen_x;
-                if draw_end_x >= width { draw_end_x = width - 1; }
-
-                if let Some((image_id, tex_rect)) = self.get_texture(&sprite.tile) {
-                    if let Some((tex_data, tex_width, _tex_height)) = world.get_image(image_id) {
-
-                        // loop through every vertical stripe of the sprite on screen
-                        let stripe = x as i32;
-                        if stripe >= draw_start_x && stripe < draw_end_x {
-                        //for stripe in draw_start_x..draw_end_x {
-                            let tex_x = ((256 * (stripe - (-sprite_width / 2 + sprite_screen_x)) * tex_rect.2 as i32 / sprite_width) / 256) as usize;
-
-                            // the conditions in the if are:
-                            // 1) it's in front of camera plane so you don't see things behind you
-                            // 2) it's on the screen (left)
-                            // 3) it's on the screen (right)
-                            // 4) ZBuffer, with perpendicular distance
-
-
-                            if transform_y > 0.0 && stripe > 0 && stripe < width && transform_y < z_buffer {
-                                for y in draw_start_y as usize .. draw_end_y as usize {
-
-                                    let d = (y - v_move_screen as usize) * 256 - height as usize * 128 + sprite_height as usize * 128; //256 and 128 factors to avoid floats
-                                    let tex_y = ((((d * tex_rect.3) / sprite_height as usize) / 256) as usize).clamp(0, tex_rect.3 as usize - 1);
-
-                                    // println!("{} {} {} {}", tex_x, tex_y, tex_rect.2, tex_rect.3);
-
-                                    let tex_off = tex_rect.0 + tex_x * 4 + tex_rect.1 + ((tex_y as usize) * *tex_width as usize * 4);
-                                    let off = y * 4;
-
+    /// Advances the animations, sprite animations, doors, light flicker and movement speeds by delta seconds
+    pub fn update(&mut self, delta: f32, world: &mut WorldMap) {
 
-                                    let mut sprite_color : [u8;4] = [0, 0, 0, 0];
-                                    sprite_color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
-                                    let tex_alpha = tex_data[tex_off+3] as f32 / 255.0;
-                                    if tex_alpha > 0.0 {
-                                        let color = self.add_lighting(&mut sprite_color, &world, (sprite.x as i32, sprite.y as i32), mix_factor * tex_alpha);
-                                        line[off..off+4].copy_from_slice(&color);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        // Update animation counter every anim_time milliseconds, advance by all frames passed since the last update
+        if self.anim_time > 0 {
+            let anim_time = self.anim_time as f32;
+            self.anim_curr_time += delta * 1000.0;
+            if !self.anim_curr_time.is_finite() {
+                self.anim_curr_time = 0.0;
             }
-        });
 
-        let hh = frame.len() / (in_stride as usize * 4);
+            let frames = (self.anim_curr_time / anim_time) as usize;
+            self.anim_curr_time %= anim_time;
+            self.anim_counter = self.anim_counter.wrapping_add(frames);
+        }
 
-        // Copy the buffer into the frame 90 degrees rotated
-        frame
-            .par_rchunks_exact_mut(in_stride as usize * 4)
-            .enumerate()
-            .for_each(|(dy, line)| {
-                let y = hh- dy;
-                if y >= in_rect.1 && y < in_rect.1 + in_rect.3 {
-                    let ry = y - in_rect.1;
-                    for x in 0..rect.2 {
-                        let off = (in_rect.0 + x) * 4;
-                        let buffer_off = ry * 4 + (rect.2 - x - 1) * 4 * stride;
-                        line[off..off+4].copy_from_slice(&buffer[buffer_off..buffer_off+4]);
-                    }
-                }
-            });
+        world.update_doors(delta);
+        world.update_lights(delta);
+        world.update_sprites(delta);
 
-        //let stop = self.get_time();
-        // println!("tick time {:?}", stop - start);
+        self.move_speed = delta * 5.0; //the constant value is in squares/second
+        self.rot_speed = delta * 2.0;
+    }
 
+    /// Calls update() with the time passed since the last call, measured with the system clock
+    pub fn tick(&mut self, world: &mut WorldMap) {
         self.old_time = self.time;
         self.time = self.get_time();
 
+        let mut frame_time = 0.0;
         if self.old_time > 0 {
-            self.anim_curr_time += self.time - self.old_time;
+            frame_time = (self.time - self.old_time) as f32 / 1000.0;
         }
-
-        let frame_time = (self.time - self.old_time) as f32 / 1000.0;
         // println!("fps {}", 1.0 / frame_time); //FPS counter
 
-        self.move_speed = frame_time * 5.0; //the constant value is in squares/second
-        self.rot_speed = frame_time * 2.0;
+        self.update(frame_time, world);
     }
 
     /// Go forward
     pub fn go_forward(&mut self, world: &WorldMap) {
-        if world.has_wall((self.pos.x + self.dir.x * self.move_speed) as i32, self.pos.y as i32) == false {
+        if self.can_move_to(world, self.pos.x + self.dir.x * self.move_speed, self.pos.y) {
             self.pos.x += self.dir.x * self.move_speed;
         }
 
-        if world.has_wall(self.pos.x as i32, (self.pos.y + self.dir.y * self.move_speed) as i32) == false {
+        if self.can_move_to(world, self.pos.x, self.pos.y + self.dir.y * self.move_speed) {
             self.pos.y += self.dir.y * self.move_speed;
         }
     }
 
     /// Go backward
     pub fn go_backward(&mut self, world: &WorldMap) {
-        if world.has_wall((self.pos.x - self.dir.x * self.move_speed) as i32, self.pos.y as i32) == false {
+        if self.can_move_to(world, self.pos.x - self.dir.x * self.move_speed, self.pos.y) {
             self.pos.x -= self.dir.x * self.move_speed;
         }
 
-        if world.has_wall(self.pos.x as i32, (self.pos.y - self.dir.y * self.move_speed) as i32) == false {
+        if self.can_move_to(world, self.pos.x, self.pos.y - self.dir.y * self.move_speed) {
             self.pos.y -= self.dir.y * self.move_speed;
         }
     }
 
+    /// Checks if the camera can move to the given position. The cell the camera is in never blocks, so a door closing on the camera can be left.
+    fn can_move_to(&self, world: &WorldMap, x: f32, y: f32) -> bool {
+        let cell = (x as i32, y as i32);
+        cell == (self.pos.x as i32, self.pos.y as i32) || !world.is_blocking(cell.0, cell.1)
+    }
+
     /// Turn left
     pub fn turn_left(&mut self) {
         let old_dir_x = self.dir.x;
@@ -937,7 +568,7 @@ impl Raycaster {
         self.dir.y = -0.0;
 
         self.plane.x = 0.0;
-        self.plane.y = 0.66;
+        self.plane.y = self.plane_length;
 
         let old_dir_x = self.dir.x;
 
@@ -958,7 +589,7 @@ impl Raycaster {
         self.dir.y = -0.0;
 
         self.plane.x = 0.0;
-        self.plane.y = 0.66;
+        self.plane.y = self.plane_length;
 
         let old_dir_x = self.dir.x;
 
@@ -979,7 +610,7 @@ impl Raycaster {
         self.dir.y = -0.0;
 
         self.plane.x = 0.0;
-        self.plane.y = 0.66;
+        self.plane.y = self.plane_length;
 
         let old_dir_x = self.dir.x;
 
@@ -1000,7 +631,7 @@ impl Raycaster {
         self.dir.y = -0.0;
 
         self.plane.x = 0.0;
-        self.plane.y = 0.66;
+        self.plane.y = self.plane_length;
     }
 
     /// Set the position
@@ -1009,7 +640,169 @@ impl Raycaster {
         self.pos.y = y;
     }
 
-    /// Set the animation time in ms
+    /// Get the position
+    pub fn get_pos(&self) -> (f32, f32) {
+        (self.pos.x, self.pos.y)
+    }
+
+    /// Get the view direction
+    pub fn get_dir(&self) -> (f32, f32) {
+        (self.dir.x, self.dir.y)
+    }
+
+    /// Casts a ray from the position in the view direction, see WorldMap::cast_ray()
+    pub fn cast_view_ray(&self, world: &WorldMap, max_distance: f32) -> RayHit {
+        world.cast_ray(self.get_pos(), self.get_dir(), max_distance)
+    }
+
+    /// Returns what is drawn at the pixel x, y of the frame when the world is rendered into the given rect, see render().
+    /// Walls and doors, the floor and the ceiling and sprites are picked with the same camera math as the renderer, the transparent texels of sprites are skipped.
+    /// Transparent walls are picked on their whole face. Returns None for pixels outside of the rect and for the horizon row.
+    pub fn pick(&self, x: usize, y: usize, rect: (usize, usize, usize, usize), world: &WorldMap) -> Option<PickHit> {
+        if x < rect.0 || y < rect.1 || x >= rect.0 + rect.2 || y >= rect.1 + rect.3 {
+            return None;
+        }
+
+        let (x, y) = ((x - rect.0) as i32, (y - rect.1) as i32);
+        let (width, height) = (rect.2 as i32, rect.3 as i32);
+
+        let scale = self.get_projection_scale(width, height);
+        let horizon = self.get_horizon(height);
+
+        // the ray of the column, the distances along it are the perpendicular distances
+        let camera_x = 2.0 * x as f32 / width as f32 - 1.0;
+        let ray_dir = (self.dir.x + self.plane.x * camera_x, self.dir.y + self.plane.y * camera_x);
+
+        // the walls and doors along the ray, like in render_column() only the rows above clip_y are visible for walls further away
+        let mut hit = None;
+        let mut clip_y = height;
+
+        world.trace_ray(self.get_pos(), ray_dir, f32::MAX, MAX_RAY_STEPS, |wall_hit| {
+            let (wall_height, transparent) = world.get_wall_cell(wall_hit.cell.0, wall_hit.cell.1)
+                .map(|wall| (wall.height, wall.transparent))
+                .unwrap_or((1.0, false));
+
+            let (_, wall_start, wall_end) = self.get_wall_rows(wall_hit.distance, wall_height, height, scale);
+            if y >= wall_start && y < wall_end.min(clip_y) {
+                let z = self.pos_z + (horizon as f32 - y as f32 - 0.5) * wall_hit.distance / scale;
+                hit = Some(PickHit {
+                    target      : if wall_hit.door { PickTarget::Door } else { PickTarget::Wall(wall_hit.face) },
+                    cell        : wall_hit.cell,
+                    position    : (wall_hit.point.0, wall_hit.point.1, z),
+                    distance    : wall_hit.distance,
+                });
+                return false;
+            }
+
+            if !transparent {
+                clip_y = clip_y.min(wall_start);
+            }
+
+            // stop if even the tallest wall behind this one would be hidden
+            let max_top = horizon - ((world.get_max_wall_height() - self.pos_z) * scale / wall_hit.distance) as i32;
+            max_top < clip_y
+        });
+
+        // otherwise the floor or the ceiling of the row
+        if hit.is_none() && y != horizon {
+            let is_floor = y > horizon;
+            let pos_z = (if is_floor { self.pos_z } else { 1.0 - self.pos_z }) * scale;
+            let row_distance = pos_z / (y - horizon).abs() as f32;
+
+            let (floor_x, floor_y) = (self.pos.x + ray_dir.0 * row_distance, self.pos.y + ray_dir.1 * row_distance);
+            hit = Some(PickHit {
+                target      : if is_floor { PickTarget::Floor } else { PickTarget::Ceiling },
+                cell        : (floor_x.floor() as i32, floor_y.floor() as i32),
+                position    : (floor_x, floor_y, if is_floor { 0.0 } else { 1.0 }),
+                distance    : row_distance,
+            });
+        }
+
+        // the nearest sprite in front of it which covers the pixel with a visible texel, sprites are always drawn over the floor and the ceiling
+        let depth = hit.filter(|hit| matches!(hit.target, PickTarget::Wall(_) | PickTarget::Door)).map(|hit| hit.distance).unwrap_or(f32::MAX);
+        let mut sprite_hit : Option<(f32, SpriteId, &Sprite, f32)> = None;
+
+        for (id, sprite) in world.sprites() {
+            let Some((transform_y, sprite_left, sprite_width, sprite_top, sprite_height)) = self.project_sprite(sprite, width, height) else { continue };
+            if transform_y >= depth || sprite_hit.is_some_and(|(d, other, _, _)| transform_y > d || (transform_y == d && id > other)) {
+                continue;
+            }
+
+            // the same columns and rows as drawn by render_column()
+            if (x as f32) < sprite_left.floor() || (x as f32) >= (sprite_left + sprite_width).floor() || x >= width - 1 {
+                continue;
+            }
+            if y < (sprite_top as i32).max(0) || y >= ((sprite_top + sprite_height) as i32).min(height - 1) {
+                continue;
+            }
+
+            if let Some((image_id, tex_rect)) = self.get_sprite_texture(sprite) {
+                let tex_x = (x as f32 - sprite_left) * tex_rect.2 as f32 / sprite_width;
+                let tex_y = ((y as f32 - sprite_top) * tex_rect.3 as f32 / sprite_height).clamp(0.0, tex_rect.3 as f32 - 0.5);
+
+                let visible = self.sample_texture(world, image_id, tex_rect, tex_x, tex_y, tex_rect.2 as f32 / sprite_width)
+                    .is_some_and(|color| color[3] as f32 / 255.0 * sprite.opacity.clamp(0.0, 1.0) > 0.0);
+
+                if visible {
+                    let z = sprite.z + sprite.scale_y * (1.0 - (y as f32 + 0.5 - sprite_top) / sprite_height);
+                    sprite_hit = Some((transform_y, id, sprite, z));
+                }
+            }
+        }
+
+        if let Some((distance, id, sprite, z)) = sprite_hit {
+            let (sprite_x, sprite_y) = (self.pos.x + ray_dir.0 * distance, self.pos.y + ray_dir.1 * distance);
+            hit = Some(PickHit {
+                target      : PickTarget::Sprite(id),
+                cell        : (sprite.x.floor() as i32, sprite.y.floor() as i32),
+                position    : (sprite_x, sprite_y, z),
+                distance,
+            });
+        }
+
+        hit
+    }
+
+    /// Set the height of the camera above the floor, 0.5 is the default and halfway between the floor and the ceiling.
+    /// Use it for jumping, crouching and head-bobbing.
+    pub fn set_pos_z(&mut self, z: f32) {
+        self.pos_z = z;
+    }
+
+    /// Set the pitch to look up (positive values) or down (negative values), in screen heights
+    pub fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch;
+    }
+
+    /// Set the horizontal field of view in degrees, the default is about 66 degrees. The angle is clamped to 1 - 179 degrees, NaN is ignored.
+    pub fn set_fov(&mut self, degrees: f32) {
+        if degrees.is_nan() {
+            return;
+        }
+        let plane_length = (degrees.clamp(1.0, 179.0).to_radians() / 2.0).tan();
+
+        self.plane.x *= plane_length / self.plane_length;
+        self.plane.y *= plane_length / self.plane_length;
+        self.plane_length = plane_length;
+    }
+
+    /// If enabled wall and sprite sizes are corrected for the aspect ratio of the render rect, so that they keep their proportions
+    /// at any aspect ratio and field of view. Otherwise they are only correct for a 4:3 rect with the default field of view.
+    pub fn set_aspect_correct(&mut self, aspect_correct: bool) {
+        self.aspect_correct = aspect_correct;
+    }
+
+    /// Set how textures are sampled
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    /// Set if the light is interpolated smoothly between the cells or if every cell is lit evenly for a blocky, retro look
+    pub fn set_smooth_lighting(&mut self, smooth_lighting: bool) {
+        self.smooth_lighting = smooth_lighting;
+    }
+
+    /// Set the animation time in ms, 0 stops the animations
     pub fn set_anim_time(&mut self, time: u16) {
         self.anim_time = time as u128;
     }
@@ -1022,6 +815,34 @@ impl Raycaster {
             stop.as_millis()
     }
 
+    #[inline(always)]
+    /// Returns the screen row of the horizon for the given screen height
+    fn get_horizon(&self, height: i32) -> i32 {
+        height / 2 + (self.pitch * height as f32) as i32
+    }
+
+    #[inline(always)]
+    /// Returns the size in pixels of one cell at a distance of one cell
+    fn get_projection_scale(&self, width: i32, height: i32) -> f32 {
+        if self.aspect_correct {
+            width as f32 / (2.0 * self.plane_length)
+        } else {
+            height as f32
+        }
+    }
+
+    #[inline(always)]
+    /// Returns the height of a one cell high line on screen and the top and the bottom row of a wall at the given perpendicular distance.
+    /// The wall stands on the floor, the bottom row is the first row below the wall.
+    fn get_wall_rows(&self, dist: f32, wall_height: f32, height: i32, scale: f32) -> (i32, i32, i32) {
+        let line_height = (scale / dist) as i32;
+
+        let wall_end = self.get_horizon(height) + (line_height as f32 * self.pos_z) as i32;
+        let wall_start = wall_end - (line_height as f32 * wall_height) as i32;
+
+        (line_height, wall_start, wall_end)
+    }
+
     #[inline(always)]
     /// Mix two colors
     fn mix_color(&self, a: &[u8;4], b: &[u8;4], v: f32) -> [u8; 4] {
@@ -1031,15 +852,211 @@ impl Raycaster {
         255]
     }
 
+    #[allow(clippy::too_many_arguments)]
+    /// Draws the visible part of a wall slice into a column of the buffer, the column starts at offset and its rows are pitch bytes apart.
+    /// Only the rows above clip_y are drawn and written into the z-buffer of the column. Returns the top row of the slice.
+    /// Transparent slices are blended over the buffer by their alpha and only pixels with an alpha above 0 are written into the z-buffer.
+    /// The slice is lit with the light at light_pos.
+    fn draw_wall_slice(&self, buffer: &mut [u8], offset: usize, pitch: usize, z_buffer: &mut [f32], world: &WorldMap, tile: &Tile, light_pos: (f32, f32), side: i32, wall_x: f32, dist: f32, wall_height: f32, height: i32, scale: f32, clip_y: i32, transparent: bool) -> i32 {
+
+        let (line_height, wall_start, wall_end) = self.get_wall_rows(dist, wall_height, height, scale);
+
+        // calculate lowest and highest pixel to fill in current stripe
+        let draw_start = wall_start.max(0);
+        let draw_end = wall_end.min(clip_y).min(height);
+
+        let mix_factor = dist / world.fog_distance;
+
+        if let Some((image_id, rect)) = self.get_texture(tile) {
+
+            // x coordinate on the texture
+            let tex_x = wall_x * rect.2 as f32;
+
+            // How much to increase the texture coordinate per screen pixel
+            let step = 1.0 * rect.3 as f32 / line_height as f32;
+
+            // the texture repeats every cell and is aligned to the floor
+            let mut tex_pos = (draw_start - wall_start) as f32 * step + (wall_height.ceil() - wall_height) * rect.3 as f32;
+
+            for y in draw_start..draw_end {
+                let off = offset + y as usize * pitch;
+
+                let Some(wall_color) = self.sample_texture(world, image_id, rect, tex_x, tex_pos, step) else { break };
+                tex_pos += step;
+
+                if transparent && wall_color[3] == 0 {
+                    continue;
+                }
+
+                let mut color = self.add_lighting(&wall_color, world, light_pos, mix_factor);
+                if transparent && wall_color[3] < 255 {
+                    let mut background : [u8;4] = [0, 0, 0, 0];
+                    background.copy_from_slice(&buffer[off..off+4]);
+                    color = self.mix_color(&background, &color, wall_color[3] as f32 / 255.0);
+                }
+                buffer[off..off+4].copy_from_slice(&color);
+
+                // perpendicular distance is stored in the z-buffer for sprite casting, sprites behind translucent texels stay visible
+                if !transparent || wall_color[3] == 255 {
+                    z_buffer[y as usize] = dist;
+                }
+            }
+        } else if let Some(color) = tile.color {
+            // Colored walls, y sides are drawn darker
+            let wall_color = if side == 1 { [color[0] / 2, color[1] / 2, color[2] / 2, color[3]] } else { color };
+            if transparent && wall_color[3] == 0 {
+                return wall_start;
+            }
+            let color = self.add_lighting(&wall_color, world, light_pos, mix_factor);
+            for y in draw_start..draw_end {
+                let off = offset + y as usize * pitch;
+                if transparent && wall_color[3] < 255 {
+                    let mut background : [u8;4] = [0, 0, 0, 0];
+                    background.copy_from_slice(&buffer[off..off+4]);
+                    let color = self.mix_color(&background, &color, wall_color[3] as f32 / 255.0);
+                    buffer[off..off+4].copy_from_slice(&color);
+                } else {
+                    buffer[off..off+4].copy_from_slice(&color);
+                }
+                if !transparent || wall_color[3] == 255 {
+                    z_buffer[y as usize] = dist;
+                }
+            }
+        }
+
+        wall_start
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Draws the decals on the given face of a wall slice on top of the wall, with the same rows and clipping as draw_wall_slice.
+    fn draw_wall_decals(&self, buffer: &mut [u8], offset: usize, pitch: usize, world: &WorldMap, face: Face, decals: &[Decal], light_pos: (f32, f32), wall_x: f32, dist: f32, wall_height: f32, height: i32, scale: f32, clip_y: i32) {
+
+        let (line_height, wall_start, wall_end) = self.get_wall_rows(dist, wall_height, height, scale);
+        if line_height <= 0 {
+            return;
+        }
+
+        let draw_start = wall_start.max(0);
+        let draw_end = wall_end.min(clip_y).min(height);
+
+        let mix_factor = dist / world.fog_distance;
+
+        for decal in decals {
+            if decal.surface != Surface::Wall(face) {
+                continue;
+            }
+
+            // the rows covered by the decal, the decal is cut off at the top of the wall
+            let top = wall_end - (line_height as f32 * (decal.rect.1 + decal.rect.3).min(wall_height)) as i32;
+            let bottom = wall_end - (line_height as f32 * decal.rect.1) as i32;
+
+            for y in top.max(draw_start)..bottom.min(draw_end) {
+                // the height above the floor of the center of the pixel
+                let z = (wall_end - y) as f32 / line_height as f32 - 0.5 / line_height as f32;
+                if let Some((u, v)) = decal.get_uv(wall_x, z) {
+                    let off = offset + y as usize * pitch;
+                    self.draw_decal_pixel(&mut buffer[off..off+4], world, decal, u, 1.0 - v, 1.0 / line_height as f32 / decal.rect.3, light_pos, mix_factor);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    /// Blends the decal at the position u, v (from 0.0 to 1.0, v points down in the texture) over the pixel.
+    /// The size is the size of the pixel in decal units, used to select the mipmap level.
+    fn draw_decal_pixel(&self, pixel: &mut [u8], world: &WorldMap, decal: &Decal, u: f32, v: f32, size: f32, light_pos: (f32, f32), mix_factor: f32) {
+        let decal_color = if let Some((image_id, rect)) = self.get_texture(&decal.tile) {
+            // clamp to the texel centers at the border, the decal does not repeat
+            let tex_x = (u * rect.2 as f32).clamp(0.5, rect.2 as f32 - 0.5);
+            let tex_y = (v * rect.3 as f32).clamp(0.5, rect.3 as f32 - 0.5);
+            self.sample_texture(world, image_id, rect, tex_x, tex_y, size * rect.2 as f32)
+        } else {
+            decal.tile.color
+        };
+
+        let Some(decal_color) = decal_color else { return };
+        if decal_color[3] == 0 {
+            return;
+        }
+
+        let mut color = self.add_lighting(&decal_color, world, light_pos, mix_factor);
+        if decal_color[3] < 255 {
+            let mut background : [u8;4] = [0, 0, 0, 0];
+            background.copy_from_slice(pixel);
+            color = self.mix_color(&background, &color, decal_color[3] as f32 / 255.0);
+        }
+        pixel.copy_from_slice(&color);
+    }
+
+    /// Projects the sprite onto a width x height screen and returns its depth, the left side and the width, the top and the height on screen.
+    /// Returns None if the sprite is behind the camera or smaller than a pixel.
+    fn project_sprite(&self, sprite: &Sprite, width: i32, height: i32) -> Option<(f32, f32, f32, f32, f32)> {
+        let (pos, dir, plane) = (self.pos, self.dir, self.plane);
+
+        // translate sprite position to relative to camera
+        let sprite_x = sprite.x - pos.x;
+        let sprite_y = sprite.y - pos.y;
+
+        // transform sprite with the inverse camera matrix
+        // [ planeX   dirX ] -1                                       [ dirY      -dirX ]
+        // [               ]       =  1/(planeX*dirY-dirX*planeY) *   [                 ]
+        // [ planeY   dirY ]                                          [ -planeY  planeX ]
+
+        let inv_det = 1.0 / (plane.x * dir.y - dir.x * plane.y); //required for correct matrix multiplication
+
+        let transform_x = inv_det * (dir.y * sprite_x - dir.x * sprite_y);
+        let transform_y = inv_det * (-plane.y * sprite_x + plane.x * sprite_y); //this is actually the depth inside the screen, that what Z is in 3D
+
+        if transform_y <= 0.0 {
+            return None;
+        }
+
+        let sprite_screen_x = (width as f32 / 2.0) * (1.0 + transform_x / transform_y);
+
+        // the size of one cell at the depth of the sprite, using 'transformY' instead of the real distance prevents fisheye
+        let cell_size = (self.get_projection_scale(width, height) / transform_y).abs();
+
+        // calculate the top and the height of the sprite on screen, world heights are projected like the walls
+        let sprite_height = cell_size * sprite.scale_y;
+        let sprite_top = self.get_horizon(height) as f32 + (self.pos_z - sprite.z - sprite.scale_y) * cell_size;
+
+        // calculate the left side and the width of the sprite on screen
+        let sprite_width = cell_size * sprite.scale_x;
+        let sprite_left = sprite_screen_x - sprite_width / 2.0;
+
+        if sprite_width < 1.0 || sprite_height < 1.0 {
+            return None;
+        }
+
+        Some((transform_y, sprite_left, sprite_width, sprite_top, sprite_height))
+    }
+
+    #[inline(always)]
+    /// Returns the tile rect of the sprite as seen from the camera, handles the animation of the sprite
+    fn get_sprite_texture(&self, sprite: &Sprite) -> Option<(usize, (usize, usize, usize, usize))> {
+        let tile = sprite.get_tile(self.pos.x, self.pos.y);
+        if let Some(animation) = &sprite.animation {
+            self.get_texture_frame(tile, animation.get_frame(tile.frames))
+        } else {
+            self.get_texture(tile)
+        }
+    }
+
     #[inline(always)]
     /// Returns the tile rect for a given texture, handles animation
     fn get_texture(&self, tile: &Tile) -> Option<(usize, (usize, usize, usize, usize))> {
+        self.get_texture_frame(tile, self.anim_counter % tile.frames.max(1) as usize)
+    }
+
+    #[inline(always)]
+    /// Returns the tile rect of the given animation frame of a texture
+    fn get_texture_frame(&self, tile: &Tile, frame: usize) -> Option<(usize, (usize, usize, usize, usize))> {
         if let Some((image_id, rect)) = tile.texture {
             if tile.frames == 1 {
                 return Some((image_id, rect));
             } else {
-                let frame = self.anim_counter % tile.frames as usize;
-                let x = rect.0 + (rect.2 * frame as usize * 4);
+                let x = rect.0 + (rect.2 * frame * 4);
                 let y = rect.1;
                 return Some((image_id, (x, y, rect.2, rect.3)));
             }
@@ -1047,21 +1064,110 @@ impl Raycaster {
         None
     }
 
-    #[inline(always)]
-    /// Adds the lighting to the pixel color
-    pub fn add_lighting(&self, color: &[u8; 4], world: &WorldMap, pos: (i32, i32), mix_factor: f32) -> [u8;4] {
-        if let Some(l) = world.light_map.get(&pos) {
+    #[allow(clippy::too_many_arguments)]
+    /// Returns the color of the sky at the given screen position. The panorama scrolls with the angle of the ray and spans half the projection scale above the horizon.
+    /// The sky fades into the fog towards the horizon.
+    fn get_sky_color(&self, world: &WorldMap, sky: &Tile, x: i32, width: i32, y: i32, horizon: i32, scale: f32) -> [u8; 4] {
 
-            let factor = ((1.0 - l) - mix_factor).clamp(0.0, 1.0);
+        // Pixels above the horizon
+        let p = (horizon - y) as f32;
 
-            let mut cr = color[0] as f32; cr -= cr * factor;
-            let mut cg = color[1] as f32; cg -= cg * factor;
-            let mut cb = color[2] as f32; cb -= cb * factor;
+        let mut color = [0, 0, 0, 255];
+        if let Some((image_id, rect)) = self.get_texture(sky) {
+            let camera_x = 2.0 * x as f32 / width as f32 - 1.0;
+            let ray_dir_x = self.dir.x + self.plane.x * camera_x;
+            let ray_dir_y = self.dir.y + self.plane.y * camera_x;
 
-            return [cr as u8, cg as u8, cb as u8, 255];
-        } else {
-            return self.mix_color(&color, &world.fog_color, mix_factor);
+            // the angle of the ray selects the column of the panorama
+            let angle = ray_dir_y.atan2(ray_dir_x);
+            let u = (0.5 - angle / (2.0 * std::f32::consts::PI)) * rect.2 as f32;
+            let v = ((1.0 - p / (scale / 2.0)) * rect.3 as f32).clamp(0.0, rect.3 as f32 - 0.5);
+
+            if let Some(sky_color) = self.sample_texture(world, image_id, rect, u, v, 1.0) {
+                color = sky_color;
+            }
+        } else if let Some(sky_color) = sky.color {
+            color = sky_color;
+        }
+
+        // use the fog of the floor row mirrored at the horizon so that both meet in the same haze
+        let mix_factor = ((self.pos_z * scale / p) / world.fog_distance).clamp(0.0, 1.0);
+        self.mix_color(&color, &world.fog_color, mix_factor)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Samples the texture rect of the image at the texel coordinates u, v of the full size image with the current filter, coordinates outside of the rect wrap around.
+    /// texel_size is the number of texels covered by one screen pixel and selects the mipmap level.
+    fn sample_texture(&self, world: &WorldMap, image_id: usize, rect: (usize, usize, usize, usize), u: f32, v: f32, texel_size: f32) -> Option<[u8; 4]> {
+        let (_, tex_width, _) = world.get_image(image_id)?;
+
+        // the position of the rect in texels
+        let rect_x = rect.0 / 4;
+        let rect_y = rect.1 / (*tex_width as usize * 4);
+
+        if self.filter == Filter::Nearest {
+            let (tex_data, _, _) = world.get_image(image_id)?;
+            let tex_x = (u.floor() as i32).rem_euclid(rect.2 as i32) as usize;
+            let tex_y = (v.floor() as i32).rem_euclid(rect.3 as i32) as usize;
+            let tex_off = (rect_x + tex_x + (rect_y + tex_y) * *tex_width as usize) * 4;
+
+            let mut color : [u8;4] = [0, 0, 0, 0];
+            color.copy_from_slice(&tex_data[tex_off..tex_off+4]);
+            return Some(color);
+        }
+
+        let mut level = 0;
+        if self.filter == Filter::Mipmap && texel_size > 1.0 {
+            level = (texel_size.log2().round() as usize).min(world.get_image_levels(image_id) - 1);
+        }
+
+        let (tex_data, level_width, _) = world.get_image_level(image_id, level)?;
+        let level_width = *level_width as usize;
+
+        let width = (rect.2 >> level).max(1) as i32;
+        let height = (rect.3 >> level).max(1) as i32;
+        let rect_x = rect_x >> level;
+        let rect_y = rect_y >> level;
+
+        // the texel centers are at .5, interpolate between the four texels around the sample position
+        let u = u / (1 << level) as f32 - 0.5;
+        let v = v / (1 << level) as f32 - 0.5;
+        let (x0, y0) = (u.floor(), v.floor());
+        let (fx, fy) = (u - x0, v - y0);
+
+        let texel = |x: i32, y: i32| -> usize {
+            (rect_x + x.rem_euclid(width) as usize + (rect_y + y.rem_euclid(height) as usize) * level_width) * 4
+        };
+
+        let offsets = [texel(x0 as i32, y0 as i32), texel(x0 as i32 + 1, y0 as i32), texel(x0 as i32, y0 as i32 + 1), texel(x0 as i32 + 1, y0 as i32 + 1)];
+        let weights = [(1.0 - fx) * (1.0 - fy), fx * (1.0 - fy), (1.0 - fx) * fy, fx * fy];
+
+        let mut color = [0.0; 4];
+        for (off, weight) in offsets.iter().zip(weights.iter()) {
+            for c in 0..4 {
+                color[c] += tex_data[off + c] as f32 * weight;
+            }
         }
+
+        Some([color[0].round() as u8, color[1].round() as u8, color[2].round() as u8, color[3].round() as u8])
+    }
+
+    #[inline(always)]
+    /// Adds the lighting to the pixel color, multiplies the color with the light at the world position pos and mixes it with the fog
+    pub fn add_lighting(&self, color: &[u8; 4], world: &WorldMap, pos: (f32, f32), mix_factor: f32) -> [u8;4] {
+        let light = if self.smooth_lighting {
+            world.sample_light(pos.0, pos.1)
+        } else {
+            world.get_light(pos.0.floor() as i32, pos.1.floor() as i32)
+        };
+
+        let lit = [
+            (color[0] as f32 * light[0]).min(255.0) as u8,
+            (color[1] as f32 * light[1]).min(255.0) as u8,
+            (color[2] as f32 * light[2]).min(255.0) as u8,
+            255];
+
+        self.mix_color(&lit, &world.fog_color, mix_factor)
     }
 
 }
\ No newline at end of file
@@ -1,5 +1,9 @@
 use crate::prelude::*;
 
+/// The handle of a sprite in the world map, stays valid until the sprite is removed. Ids are not reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpriteId(pub u32);
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Sprite {
 
@@ -8,15 +12,28 @@ pub struct Sprite {
 
     pub tile            : Tile,
 
-    /// Shrinks the sprite
-    pub shrink          : i32,
+    /// The facing of the sprite in degrees, 0 faces east (+x) and 90 north (+y)
+    pub angle           : f32,
+
+    /// Optional tiles for the views of the sprite from different directions. The first tile shows the front of the sprite,
+    /// the following tiles show the views from directions further counterclockwise around it, with 8 tiles the third tile shows the left side.
+    /// The tile is used for all directions if there are no rotations.
+    pub rotations       : Vec<Tile>,
+
+    /// The width of the sprite relative to a cell, 1.0 is as wide as a cell
+    pub scale_x         : f32,
 
-    /// Moves the sprite up and down
-    pub move_y          : f32,
+    /// The height of the sprite relative to a cell, 1.0 is as high as a wall
+    pub scale_y         : f32,
 
-    /// Distance from the player, used for sorting the sprites
-    /// Only used internally
-    pub distance        : f32,
+    /// The height of the bottom of the sprite above the floor in world units, 0.0 stands on the floor
+    pub z               : f32,
+
+    /// The opacity of the sprite from 0.0 (invisible) to 1.0 (opaque), multiplied with the alpha of the texels
+    pub opacity         : f32,
+
+    /// Optional timing of the frames of the sprite tiles, without an animation the sprite uses the global animation time of the Raycaster
+    pub animation       : Option<Animation>,
 }
 
 /// A tile
@@ -26,9 +43,60 @@ impl Sprite {
         Self {
             x, y,
             tile,
-            shrink      : 1,
-            move_y      : 0.0,
-            distance    : 0.0,
+            angle       : 0.0,
+            rotations   : vec![],
+            scale_x     : 1.0,
+            scale_y     : 1.0,
+            z           : 0.0,
+            opacity     : 1.0,
+            animation   : None,
         }
     }
-}
\ No newline at end of file
+
+    /// Creates a new sprite facing the given angle in degrees, which shows one of the rotation tiles depending on the direction it is seen from.
+    /// The rotation tiles start with the front view and go counterclockwise around the sprite.
+    /// Returns None if there are no rotation tiles.
+    pub fn directional(x: f32, y: f32, rotations: Vec<Tile>, angle: f32) -> Option<Self> {
+        let mut sprite = Self::new(x, y, rotations.first()?.clone());
+        sprite.angle = angle;
+        sprite.rotations = rotations;
+        Some(sprite)
+    }
+
+    /// Scales the width and the height of the sprite by the same factor
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale_x = scale;
+        self.scale_y = scale;
+    }
+
+    /// Starts the given animation for the frames of the sprite tiles
+    pub fn play(&mut self, animation: Animation) {
+        self.animation = Some(animation);
+    }
+
+    /// Returns true if the play once animation of the sprite has finished, based on the frames of the tile shown to a viewer at the given position.
+    /// Sprites without an animation or with a looping animation never finish.
+    pub fn is_animation_finished(&self, view_x: f32, view_y: f32) -> bool {
+        if let Some(animation) = &self.animation {
+            animation.is_finished(self.get_tile(view_x, view_y).frames)
+        } else {
+            false
+        }
+    }
+
+    /// Returns the tile showing the sprite as seen from the given position
+    pub fn get_tile(&self, view_x: f32, view_y: f32) -> &Tile {
+        if self.rotations.is_empty() {
+            return &self.tile;
+        }
+
+        // the angle of the viewer relative to the facing of the sprite
+        let view_angle = (view_y - self.y).atan2(view_x - self.x).to_degrees() - self.angle;
+
+        let count = self.rotations.len();
+        let segment = 360.0 / count as f32;
+        let index = (view_angle.rem_euclid(360.0) / segment).round() as usize % count;
+
+        &self.rotations[index]
+    }
+}
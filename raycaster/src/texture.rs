@@ -0,0 +1,45 @@
+/// How textures are sampled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// The nearest texel, the classic blocky look
+    Nearest,
+    /// Bilinear interpolation of the four nearest texels
+    Bilinear,
+    /// Bilinear interpolation in the mipmap level matching the distance, avoids the shimmering of distant textures
+    Mipmap,
+}
+
+/// Generates the mip chain of an RGBA image, the first entry is the image at half size.
+/// Every level halves the previous one, the chain stops when the width or height is odd so that the tiles of an atlas stay aligned.
+pub fn generate_mipmaps(data: &[u8], width: u32, height: u32) -> Vec<(Vec<u8>, u32, u32)> {
+    let mut levels : Vec<(Vec<u8>, u32, u32)> = vec![];
+
+    let mut src_width = width as usize;
+    let mut src_height = height as usize;
+
+    while src_width.is_multiple_of(2) && src_height.is_multiple_of(2) && src_width > 1 && src_height > 1 {
+        let src = levels.last().map(|level| &level.0[..]).unwrap_or(data);
+
+        let w = src_width / 2;
+        let h = src_height / 2;
+
+        // average each 2x2 block of the previous level
+        let mut level = vec![0; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let off = (x * 2 + y * 2 * src_width) * 4;
+                for c in 0..4 {
+                    let sum = src[off + c] as u32 + src[off + 4 + c] as u32 + src[off + src_width * 4 + c] as u32 + src[off + src_width * 4 + 4 + c] as u32;
+                    level[(x + y * w) * 4 + c] = ((sum + 2) / 4) as u8;
+                }
+            }
+        }
+
+        levels.push((level, w as u32, h as u32));
+
+        src_width = w;
+        src_height = h;
+    }
+
+    levels
+}
@@ -23,7 +23,7 @@ impl Tile {
         Self {
             color       : None,
             texture     : Some((image_id, rect)),
-            frames      : frames,
+            frames,
         }
     }
 
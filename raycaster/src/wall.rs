@@ -0,0 +1,71 @@
+use crate::prelude::*;
+
+/// The faces of a wall cell, named after the direction they are facing (the same directions as Raycaster::face_north() etc.)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Face {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Face {
+    /// Returns the face hit by a ray for the given DDA side and ray step direction
+    pub fn from_ray(side: i32, step_x: i32, step_y: i32) -> Self {
+        if side == 0 {
+            if step_x > 0 { Face::West } else { Face::East }
+        } else if step_y > 0 {
+            Face::South
+        } else {
+            Face::North
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Face::North => 0,
+            Face::East  => 1,
+            Face::South => 2,
+            Face::West  => 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wall {
+
+    /// The tile used for all faces which do not have their own tile
+    pub tile                : Tile,
+
+    /// Optional tiles for the north, east, south and west faces
+    pub faces               : [Option<Tile>; 4],
+
+    /// The height of the wall in cells, the default is 1.0
+    pub height              : f32,
+
+    /// Transparent walls let the rays pass, the cells behind them are drawn first and the alpha masked wall on top
+    pub transparent         : bool,
+}
+
+/// A wall cell
+impl Wall {
+    /// Creates a new wall which uses the given tile for all faces
+    pub fn new(tile: Tile) -> Self {
+        Self {
+            tile,
+            faces       : [None, None, None, None],
+            height      : 1.0,
+            transparent : false,
+        }
+    }
+
+    /// Sets the tile for the given face
+    pub fn set_face(&mut self, face: Face, tile: Tile) {
+        self.faces[face.index()] = Some(tile);
+    }
+
+    /// Gets the tile for the given face, falls back to the wall tile
+    pub fn get_face(&self, face: Face) -> &Tile {
+        self.faces[face.index()].as_ref().unwrap_or(&self.tile)
+    }
+}
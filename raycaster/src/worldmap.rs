@@ -1,23 +1,56 @@
 use crate::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rustc_hash::FxHashSet;
 
 pub struct WorldMap {
-    walls                   : FxHashMap<(i32, i32), Tile>,
+    walls                   : FxHashMap<(i32, i32), Wall>,
     floors                  : FxHashMap<(i32, i32), Tile>,
     ceilings                : FxHashMap<(i32, i32), Tile>,
+    open_ceilings           : FxHashSet<(i32, i32)>,
+    doors                   : FxHashMap<(i32, i32), Door>,
+    decals                  : FxHashMap<(i32, i32), Vec<Decal>>,
 
     images                  : Vec<(Vec<u8>, u32, u32)>,
 
-    pub sprites             : Vec<Sprite>,
+    /// The mip chains of the images, starting with the half size level
+    mipmaps                 : Vec<Vec<(Vec<u8>, u32, u32)>>,
+
+    sprites                 : FxHashMap<SpriteId, Sprite>,
+
+    /// The id of the next added sprite
+    next_sprite_id          : u32,
 
     ceiling_tile            : Option<Tile>,
+
+    /// The sky panorama, drawn where there is no ceiling
+    sky_tile                : Option<Tile>,
     floor_tile              : Option<Tile>,
 
     pub fog_color           : [u8;4],
     pub fog_distance        : f32,
 
+    max_wall_height         : f32,
+
+    /// The light level of all cells, the lights are added on top of it
+    pub ambient             : f32,
+
+    /// The lights by cell. Use the light functions to change them, after direct changes call compute_lighting() to update the light map
     pub lights              : FxHashMap<(i32, i32), Light>,
-    pub light_map           : FxHashMap<(i32, i32), f32>
+
+    /// The summed up RGB light of all lights per cell, without the ambient level
+    pub light_map           : FxHashMap<(i32, i32), [f32;3]>,
+
+    /// True if the light map has to be recomputed
+    lighting_dirty          : bool,
+
+    /// The random number generator for the light flicker
+    rng                     : StdRng,
+}
+
+impl Default for WorldMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// The world map
@@ -28,37 +61,169 @@ impl WorldMap {
             walls           : FxHashMap::default(),
             floors          : FxHashMap::default(),
             ceilings        : FxHashMap::default(),
+            open_ceilings   : FxHashSet::default(),
+            doors           : FxHashMap::default(),
+            decals          : FxHashMap::default(),
 
             images          : vec![],
+            mipmaps         : vec![],
 
-            sprites         : vec![],
+            sprites         : FxHashMap::default(),
+            next_sprite_id  : 0,
 
             ceiling_tile    : None,
+            sky_tile        : None,
             floor_tile      : None,
 
             fog_color       : [0, 0, 0, 255],
             fog_distance    : 6.0,
 
+            max_wall_height : 1.0,
+
+            ambient         : 1.0,
+
             lights          : FxHashMap::default(),
             light_map       : FxHashMap::default(),
+
+            lighting_dirty  : true,
+
+            rng             : StdRng::seed_from_u64(0),
         }
     }
 
     /// Sets a wall at the given position
     pub fn set_wall(&mut self, x: i32, y: i32, tile: Tile) {
-        self.walls.insert((x, y), tile);
+        self.walls.insert((x, y), Wall::new(tile));
+        self.lighting_dirty = true;
+    }
+
+    /// Sets the tile of a single face of the wall at the given position, the other faces keep their tiles.
+    /// If there is no wall at the position, a wall using the tile for all faces is created first.
+    pub fn set_wall_face(&mut self, x: i32, y: i32, face: Face, tile: Tile) {
+        if !self.walls.contains_key(&(x, y)) {
+            self.lighting_dirty = true;
+        }
+        self.walls.entry((x, y)).or_insert_with(|| Wall::new(tile.clone())).set_face(face, tile);
+    }
+
+    /// Sets the height of the wall at the given position, walls are 1.0 cells high by default.
+    /// Walls behind lower walls stay visible if they are taller.
+    pub fn set_wall_height(&mut self, x: i32, y: i32, height: f32) {
+        if let Some(wall) = self.walls.get_mut(&(x, y)) {
+            wall.height = height;
+            self.max_wall_height = self.max_wall_height.max(height);
+        }
+    }
+
+    /// Makes the wall at the given position transparent (for grates, windows or fences).
+    /// Texels with an alpha of 0 show the cells behind the wall, other texels are blended by their alpha.
+    pub fn set_wall_transparent(&mut self, x: i32, y: i32, transparent: bool) {
+        if let Some(wall) = self.walls.get_mut(&(x, y)) {
+            wall.transparent = transparent;
+            self.lighting_dirty = true;
+        }
+    }
+
+    /// Gets the height of the tallest wall, used to stop the ray casting early
+    pub fn get_max_wall_height(&self) -> f32 {
+        self.max_wall_height
     }
 
     /// Checks if there is a wall at the given position
     pub fn has_wall(&self, x: i32, y: i32) -> bool {
-        self.walls.get(&(x, y)).is_some()
+        self.walls.contains_key(&(x, y))
     }
 
-    /// Gets the wall at the given position
+    /// Gets the wall tile at the given position
     pub fn get_wall(&self, x: i32, y: i32) -> Option<&Tile> {
+        self.walls.get(&(x, y)).map(|wall| &wall.tile)
+    }
+
+    /// Gets the wall cell at the given position
+    pub fn get_wall_cell(&self, x: i32, y: i32) -> Option<&Wall> {
         self.walls.get(&(x, y))
     }
 
+    /// Gets the tile for the given face of the wall at the given position
+    pub fn get_wall_face(&self, x: i32, y: i32, face: Face) -> Option<&Tile> {
+        self.walls.get(&(x, y)).map(|wall| wall.get_face(face))
+    }
+
+    /// Sets a door at the given position
+    pub fn set_door(&mut self, x: i32, y: i32, door: Door) {
+        self.doors.insert((x, y), door);
+        self.lighting_dirty = true;
+    }
+
+    /// Checks if there is a door at the given position
+    pub fn has_door(&self, x: i32, y: i32) -> bool {
+        self.doors.contains_key(&(x, y))
+    }
+
+    /// Gets the door at the given position
+    pub fn get_door(&self, x: i32, y: i32) -> Option<&Door> {
+        self.doors.get(&(x, y))
+    }
+
+    /// Gets the mutable door at the given position
+    pub fn get_door_mut(&mut self, x: i32, y: i32) -> Option<&mut Door> {
+        self.doors.get_mut(&(x, y))
+    }
+
+    /// Starts opening the door at the given position
+    pub fn open_door(&mut self, x: i32, y: i32) {
+        if let Some(door) = self.doors.get_mut(&(x, y)) {
+            door.open();
+        }
+    }
+
+    /// Starts closing the door at the given position
+    pub fn close_door(&mut self, x: i32, y: i32) {
+        if let Some(door) = self.doors.get_mut(&(x, y)) {
+            door.close();
+        }
+    }
+
+    /// Toggles the door at the given position
+    pub fn toggle_door(&mut self, x: i32, y: i32) {
+        if let Some(door) = self.doors.get_mut(&(x, y)) {
+            door.toggle();
+        }
+    }
+
+    /// Advances all door animations by delta seconds
+    pub fn update_doors(&mut self, delta: f32) {
+        for door in self.doors.values_mut() {
+            // moving doors change the lighting
+            if door.state == DoorState::Opening || door.state == DoorState::Closing {
+                self.lighting_dirty = true;
+            }
+            door.update(delta);
+        }
+    }
+
+    /// Checks if the given position blocks movement, i.e. is a wall or a door which is not fully open
+    pub fn is_blocking(&self, x: i32, y: i32) -> bool {
+        if self.has_wall(x, y) {
+            return true;
+        }
+        if let Some(door) = self.doors.get(&(x, y)) {
+            return !door.is_passable();
+        }
+        false
+    }
+
+    /// Checks if the cell at the given position stops light, i.e. is an opaque wall or a closed door
+    pub fn blocks_light(&self, x: i32, y: i32) -> bool {
+        if let Some(wall) = self.walls.get(&(x, y)) {
+            return !wall.transparent;
+        }
+        if let Some(door) = self.doors.get(&(x, y)) {
+            return door.state == DoorState::Closed;
+        }
+        false
+    }
+
     /// Sets the ceiling tile
     pub fn set_default_ceiling(&mut self, tile: Tile) {
         self.ceiling_tile = Some(tile);
@@ -76,7 +241,7 @@ impl WorldMap {
 
     /// Checks if there is a ceiling at the given position
     pub fn has_ceiling(&self, x: i32, y: i32) -> bool {
-        self.ceilings.get(&(x, y)).is_some()
+        self.ceilings.contains_key(&(x, y))
     }
 
     /// Gets the ceiling at the given position
@@ -84,6 +249,30 @@ impl WorldMap {
         self.ceilings.get(&(x, y))
     }
 
+    /// Opens the ceiling at the given position to the sky, neither the default nor a cell ceiling is drawn there
+    pub fn set_ceiling_open(&mut self, x: i32, y: i32, open: bool) {
+        if open {
+            self.open_ceilings.insert((x, y));
+        } else {
+            self.open_ceilings.remove(&(x, y));
+        }
+    }
+
+    /// Checks if the ceiling at the given position is open to the sky
+    pub fn is_ceiling_open(&self, x: i32, y: i32) -> bool {
+        self.open_ceilings.contains(&(x, y))
+    }
+
+    /// Sets the sky, a panorama covering 360 degrees horizontally from the horizon upwards. It is drawn where there is no ceiling.
+    pub fn set_sky(&mut self, tile: Tile) {
+        self.sky_tile = Some(tile);
+    }
+
+    /// Gets the sky tile
+    pub fn get_sky(&self) -> Option<&Tile> {
+        self.sky_tile.as_ref()
+    }
+
     /// Sets the floor tile
     pub fn set_default_floor(&mut self, tile: Tile) {
         self.floor_tile = Some(tile);
@@ -101,7 +290,7 @@ impl WorldMap {
 
     /// Checks if there is a floor at the given position
     pub fn has_floor(&self, x: i32, y: i32) -> bool {
-        self.floors.get(&(x, y)).is_some()
+        self.floors.contains_key(&(x, y))
     }
 
     /// Gets the floor at the given position
@@ -109,9 +298,30 @@ impl WorldMap {
         self.floors.get(&(x, y))
     }
 
+    /// Adds a decal to the wall or the floor of the given cell, decals added later are drawn on top
+    pub fn add_decal(&mut self, x: i32, y: i32, decal: Decal) {
+        self.decals.entry((x, y)).or_default().push(decal);
+    }
+
+    /// Removes all decals of the given cell
+    pub fn remove_decals(&mut self, x: i32, y: i32) {
+        self.decals.remove(&(x, y));
+    }
+
+    /// Returns the decals of the given cell
+    pub fn get_decals(&self, x: i32, y: i32) -> &[Decal] {
+        self.decals.get(&(x, y)).map(|decals| &decals[..]).unwrap_or(&[])
+    }
+
+    /// Returns true if there are any decals
+    pub fn has_decals(&self) -> bool {
+        !self.decals.is_empty()
+    }
+
     /// Adds an image to the list of images
     pub fn add_image(&mut self, data: Vec<u8>, width: u32, height: u32) -> usize {
         let index = self.images.len();
+        self.mipmaps.push(crate::texture::generate_mipmaps(&data, width, height));
         self.images.push((data, width, height));
         index
     }
@@ -121,9 +331,70 @@ impl WorldMap {
         self.images.get(index)
     }
 
-    /// Adds a sprite to the list of sprites
-    pub fn add_sprite(&mut self, sprite: Sprite) {
-        self.sprites.push(sprite);
+    /// Gets the given mipmap level of the image at the given index, level 0 is the image itself
+    pub fn get_image_level(&self, index: usize, level: usize) -> Option<&(Vec<u8>, u32, u32)> {
+        if level == 0 {
+            self.images.get(index)
+        } else {
+            self.mipmaps.get(index).and_then(|levels| levels.get(level - 1))
+        }
+    }
+
+    /// Gets the number of mipmap levels of the image at the given index, including the image itself
+    pub fn get_image_levels(&self, index: usize) -> usize {
+        self.mipmaps.get(index).map(|levels| levels.len() + 1).unwrap_or(0)
+    }
+
+    /// Adds a sprite and returns its id
+    pub fn add_sprite(&mut self, sprite: Sprite) -> SpriteId {
+        let id = SpriteId(self.next_sprite_id);
+        self.next_sprite_id += 1;
+        self.sprites.insert(id, sprite);
+        id
+    }
+
+    /// Removes the sprite with the given id and returns it
+    pub fn remove_sprite(&mut self, id: SpriteId) -> Option<Sprite> {
+        self.sprites.remove(&id)
+    }
+
+    /// Removes all sprites
+    pub fn clear_sprites(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Returns the sprite with the given id
+    pub fn get_sprite(&self, id: SpriteId) -> Option<&Sprite> {
+        self.sprites.get(&id)
+    }
+
+    /// Returns the sprite with the given id for modification
+    pub fn get_sprite_mut(&mut self, id: SpriteId) -> Option<&mut Sprite> {
+        self.sprites.get_mut(&id)
+    }
+
+    /// Returns the number of sprites
+    pub fn sprite_count(&self) -> usize {
+        self.sprites.len()
+    }
+
+    /// Iterates over the ids and sprites, in no particular order
+    pub fn sprites(&self) -> impl Iterator<Item = (SpriteId, &Sprite)> {
+        self.sprites.iter().map(|(id, sprite)| (*id, sprite))
+    }
+
+    /// Iterates over the ids and sprites for modification, in no particular order
+    pub fn sprites_mut(&mut self) -> impl Iterator<Item = (SpriteId, &mut Sprite)> {
+        self.sprites.iter_mut().map(|(id, sprite)| (*id, sprite))
+    }
+
+    /// Advances the animations of all sprites by delta seconds
+    pub fn update_sprites(&mut self, delta: f32) {
+        for sprite in self.sprites.values_mut() {
+            if let Some(animation) = &mut sprite.animation {
+                animation.update(delta);
+            }
+        }
     }
 
     /// Set the fog color and distance
@@ -132,69 +403,174 @@ impl WorldMap {
         self.fog_distance = distance;
     }
 
-    /// Add a light
+    /// Set the ambient light level, 1.0 shows the textures unchanged, lower values darken the cells outside of the lights
+    pub fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+
+    /// Add a white light
     pub fn add_light(&mut self, x: i32, y: i32, intensity: i32) {
         let light = Light::new(intensity);
         self.lights.insert((x, y), light);
+        self.lighting_dirty = true;
     }
 
-    pub fn compute_lighting(&mut self) {
-        let mut map : FxHashMap<(i32, i32), f32> = FxHashMap::default();
+    /// Add a colored light
+    pub fn add_colored_light(&mut self, x: i32, y: i32, intensity: i32, color: [u8;4]) {
+        let light = Light::colored(intensity, color);
+        self.lights.insert((x, y), light);
+        self.lighting_dirty = true;
+    }
+
+    /// Sets the flicker of the light at the given position. The amplitude is the maximum random change of the light strength, 0.0 makes the light steady.
+    /// The frequency is the number of flicker changes per second.
+    pub fn set_light_flicker(&mut self, x: i32, y: i32, amplitude: f32, frequency: f32) {
+        if let Some(light) = self.lights.get_mut(&(x, y)) {
+            light.flicker_amplitude = amplitude;
+            light.flicker_frequency = frequency;
+            if amplitude == 0.0 {
+                light.flicker = 0.0;
+            }
+            self.lighting_dirty = true;
+        }
+    }
 
-        let mut rng = thread_rng();
+    /// Seeds the random number generator of the light flicker, worlds with the same seed and updates flicker the same way
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Advances the light flicker by delta seconds and recomputes the light map if the flicker, the walls, the doors or the lights changed
+    pub fn update_lights(&mut self, delta: f32) {
+        // sort the lights by position so that the random numbers are drawn in the same order for every world
+        let mut positions : Vec<(i32, i32)> = self.lights.keys().cloned().collect();
+        positions.sort();
+
+        for pos in positions {
+            if let Some(light) = self.lights.get_mut(&pos) {
+                if light.flicker_amplitude <= 0.0 || light.flicker_frequency <= 0.0 {
+                    continue;
+                }
+
+                light.flicker_time += delta;
+                let interval = 1.0 / light.flicker_frequency;
+                if light.flicker_time >= interval {
+                    light.flicker_time %= interval;
+                    light.flicker = self.rng.gen_range(-light.flicker_amplitude..=light.flicker_amplitude);
+                    self.lighting_dirty = true;
+                }
+            }
+        }
+
+        if self.lighting_dirty {
+            self.compute_lighting();
+        }
+    }
+
+    /// Gets the RGB light of the cell at the given position, the ambient level plus the light of all lights reaching the cell.
+    /// The lights brighten the cells up to 1.0, which shows the textures unchanged, so they have no effect with the default ambient level.
+    pub fn get_light(&self, x: i32, y: i32) -> [f32;3] {
+        let mut light = [self.ambient; 3];
+        if let Some(l) = self.light_map.get(&(x, y)) {
+            let max = self.ambient.max(1.0);
+            light[0] = (light[0] + l[0]).min(max);
+            light[1] = (light[1] + l[1]).min(max);
+            light[2] = (light[2] + l[2]).min(max);
+        }
+        light
+    }
+
+    /// Samples the RGB light at the given world position, interpolates bilinearly between the lights of the four nearest cell centers.
+    /// Cells which block the light are left out so that the light of a wall does not spread to its other side.
+    pub fn sample_light(&self, x: f32, y: f32) -> [f32;3] {
+        if self.light_map.is_empty() {
+            return [self.ambient; 3];
+        }
+
+        let (sx, sy) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (cx, cy) = (x0 as i32, y0 as i32);
+
+        let mut light = [0.0; 3];
+        let mut total = 0.0;
+
+        for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+            if weight <= 0.0 || self.blocks_light(cx + dx, cy + dy) {
+                continue;
+            }
+            let l = self.get_light(cx + dx, cy + dy);
+            for c in 0..3 {
+                light[c] += l[c] * weight;
+            }
+            total += weight;
+        }
+
+        if total <= 0.0 {
+            return self.get_light(x.floor() as i32, y.floor() as i32);
+        }
+
+        light.map(|l| l / total)
+    }
+
+    /// Computes the light map. The light spreads from each light cell ring by ring up to its intensity, it flows around walls and closed doors and only leaks through openings.
+    /// Walls and doors stop the light, their cells are lit but the renderer lights each face with the cell in front of it. Transparent walls let the light pass.
+    pub fn compute_lighting(&mut self) {
+        let mut map : FxHashMap<(i32, i32), [f32;3]> = FxHashMap::default();
 
         for (pos, l) in &self.lights {
-            map.insert(pos.clone(), 1.0);
+            let color = [l.color[0] as f32 / 255.0, l.color[1] as f32 / 255.0, l.color[2] as f32 / 255.0];
 
-            if l.intensity > 0 {
-                let mut tl = (pos.0 - 1, pos.1 - 1);
-                let mut length = 3;
+            // colored lights are added up
+            let mut add_light = |cell: (i32, i32), i: f32| {
+                let value = map.entry(cell).or_insert([0.0; 3]);
+                value[0] += color[0] * i;
+                value[1] += color[1] * i;
+                value[2] += color[2] * i;
+            };
 
-                let mut d = 1;
+            add_light(*pos, 1.0);
 
-                let mut random : f32 = rng.gen();
-                random -= 0.5;
-                random *= 0.3;
+            if l.intensity > 0 {
+                let mut visited : FxHashSet<(i32, i32)> = FxHashSet::default();
+                visited.insert(*pos);
 
-                while d < l.intensity {
+                // the cells at the current distance from the light
+                let mut ring = vec![*pos];
 
-                    let i = 1.0 / (d*2) as f32 + random / d as f32;
-                    for x in tl.0..tl.0 + length {
-                        if let Some(value) = map.get_mut(&(x, tl.1)) {
-                            *value += i;
-                        } else {
-                            map.insert((x, tl.1), i);
-                        }
+                let mut d = 1;
 
-                        if let Some(value) = map.get_mut(&(x, tl.1 + length - 1)) {
-                            *value += i;
-                        } else {
-                            map.insert((x, tl.1 + length - 1), i);
-                        }
-                    }
+                while d < l.intensity && !ring.is_empty() {
+
+                    let i = 1.0 / (d*2) as f32 + l.flicker / d as f32;
+                    let mut next_ring = vec![];
 
-                    for y in tl.1+1..tl.1 + length - 1 {
-                        if let Some(value) = map.get_mut(&(tl.0, y)) {
-                            *value += i;
-                        } else {
-                            map.insert((tl.0, y), i);
+                    for cell in &ring {
+                        if cell != pos && self.blocks_light(cell.0, cell.1) {
+                            continue;
                         }
 
-                        if let Some(value) = map.get_mut(&(tl.0 + length - 1, y)) {
-                            *value += i;
-                        } else {
-                            map.insert((tl.0 + length - 1, y), i);
+                        for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                            // the light does not slip diagonally past the corner of a wall
+                            if dx != 0 && dy != 0 && (self.blocks_light(cell.0 + dx, cell.1) || self.blocks_light(cell.0, cell.1 + dy)) {
+                                continue;
+                            }
+
+                            let next = (cell.0 + dx, cell.1 + dy);
+                            if visited.insert(next) {
+                                add_light(next, i);
+                                next_ring.push(next);
+                            }
                         }
                     }
 
+                    ring = next_ring;
                     d += 1;
-                    length += 2;
-                    tl.0 -= 1;
-                    tl.1 -= 1;
                 }
             }
         }
 
         self.light_map = map;
+        self.lighting_dirty = false;
     }
 }
\ No newline at end of file
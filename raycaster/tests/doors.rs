@@ -0,0 +1,58 @@
+//! Door movement tests.
+
+use raycaster::prelude::*;
+
+/// Creates a corridor along y = 3 with a vertical door at (3, 3)
+fn create_corridor() -> WorldMap {
+    let mut world = WorldMap::new();
+    let tile = Tile::colored([128, 128, 128, 255]);
+
+    for x in 0..8 {
+        world.set_wall(x, 2, tile.clone());
+        world.set_wall(x, 4, tile.clone());
+    }
+    world.set_door(3, 3, Door::new(Tile::colored([90, 60, 30, 255]), true));
+
+    world
+}
+
+#[test]
+fn closed_doors_block_the_player() {
+    let mut world = create_corridor();
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+    caster.update(0.1, &mut world);
+
+    for _ in 0..10 {
+        caster.go_forward(&world);
+    }
+    assert!(caster.get_pos().0 < 3.0);
+}
+
+#[test]
+fn a_closing_door_does_not_trap_the_player() {
+    let mut world = create_corridor();
+    world.open_door(3, 3);
+    world.update_doors(10.0);
+
+    // the player stands in the open door when it starts closing
+    let mut caster = Raycaster::new();
+    caster.set_pos(3.5, 3.5);
+    caster.face_east();
+    world.close_door(3, 3);
+    caster.update(0.1, &mut world);
+
+    for _ in 0..5 {
+        caster.go_forward(&world);
+    }
+    assert!(caster.get_pos().0 >= 4.0, "the player leaves the door cell: {:?}", caster.get_pos());
+
+    // and can not walk back into the closed door
+    world.update_doors(10.0);
+    caster.face_west();
+    for _ in 0..5 {
+        caster.go_forward(&world);
+    }
+    assert!(caster.get_pos().0 >= 4.0);
+}
@@ -0,0 +1,331 @@
+//! Golden image tests, renders a few reference scenes with both renderers and compares them to the PNGs in tests/golden.
+//! Both renderers share the same reference image.
+//! Run with UPDATE_GOLDEN=1 to write new reference images after an intended change in the output.
+
+use raycaster::prelude::*;
+use raycaster::headless::{save_png, load_png};
+
+use std::path::PathBuf;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 100;
+
+/// The maximum difference of a color channel before a pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 2;
+
+/// The maximum ratio of different pixels before a test fails
+const PIXEL_TOLERANCE: f32 = 0.001;
+
+const TILE_SIZE: usize = 16;
+
+/// Returns the texture rect of the given tile in the test atlas
+fn tile_rect(index: usize) -> (usize, usize, usize, usize) {
+    (index * TILE_SIZE * 4, 0, TILE_SIZE, TILE_SIZE)
+}
+
+/// Creates a procedural atlas of four tiles: bricks, checkers, floor planks and a round sprite
+fn create_atlas() -> (Vec<u8>, u32, u32) {
+    let width = TILE_SIZE * 4;
+    let mut data = vec![0; width * TILE_SIZE * 4];
+
+    for y in 0..TILE_SIZE {
+        for x in 0..TILE_SIZE {
+            let offset = if (y / 4) % 2 == 0 { 0 } else { 4 };
+            let brick = if y % 4 == 3 || (x + offset) % 8 == 7 { [90, 90, 90, 255] } else { [160, 60 + (x * 3) as u8, 40, 255] };
+
+            let checker = if (x / 4 + y / 4) % 2 == 0 { [230, 230, 230, 255] } else { [40, 60, 200, 255] };
+
+            let plank = if x % 5 == 4 { [60, 40, 20, 255] } else { [140 - (y * 2) as u8, 100, 50, 255] };
+
+            let dx = x as f32 - 7.5;
+            let dy = y as f32 - 7.5;
+            let sprite = if dx * dx + dy * dy < 36.0 { [40, 200 - (y * 8) as u8, 60, 255] } else { [0, 0, 0, 0] };
+
+            for (index, color) in [brick, checker, plank, sprite].iter().enumerate() {
+                let off = (index * TILE_SIZE + x) * 4 + y * width * 4;
+                data[off..off + 4].copy_from_slice(color);
+            }
+        }
+    }
+
+    (data, width as u32, TILE_SIZE as u32)
+}
+
+/// Creates a world with a brick walled room of the given size and checkered pillars at the given positions
+fn create_room(size: (i32, i32), pillars: &[(i32, i32)]) -> WorldMap {
+    let mut world = WorldMap::new();
+
+    let (data, width, height) = create_atlas();
+    let image_id = world.add_image(data, width, height);
+
+    for y in 0..size.1 {
+        for x in 0..size.0 {
+            if x == 0 || y == 0 || x == size.0 - 1 || y == size.1 - 1 {
+                world.set_wall(x, y, Tile::textured(image_id, tile_rect(0)));
+            }
+        }
+    }
+
+    for (x, y) in pillars {
+        world.set_wall(*x, *y, Tile::textured(image_id, tile_rect(1)));
+    }
+
+    world.set_default_floor(Tile::textured(image_id, tile_rect(2)));
+    world.set_default_ceiling(Tile::textured(image_id, tile_rect(1)));
+    world.set_fog([0, 0, 0, 255], 1000.0);
+
+    world
+}
+
+/// Renders the world with both renderers and compares the results to the reference image
+fn check_scene(name: &str, caster: &Raycaster, world: &WorldMap) {
+    let mut st = vec![0; WIDTH * HEIGHT * 4];
+    caster.render_st(&mut st[..], (0, 0, WIDTH, HEIGHT), WIDTH, world);
+    check_image(name, &st);
+
+    let mut mt = vec![0; WIDTH * HEIGHT * 4];
+    caster.render_mt(&mut mt[..], (0, 0, WIDTH, HEIGHT), WIDTH, world);
+    check_image(name, &mt);
+}
+
+/// Compares the image to the reference image with the given name, writes the reference instead if UPDATE_GOLDEN is set
+fn check_image(name: &str, buffer: &[u8]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        save_png(&path, buffer, WIDTH, HEIGHT).unwrap();
+        return;
+    }
+
+    let (reference, width, height) = load_png(&path)
+        .unwrap_or_else(|e| panic!("Could not load {}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    assert_eq!((width as usize, height as usize), (WIDTH, HEIGHT), "{}: size mismatch", name);
+
+    let different = reference.chunks_exact(4).zip(buffer.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+        .count();
+
+    if different as f32 > (WIDTH * HEIGHT) as f32 * PIXEL_TOLERANCE {
+        let actual = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+        save_png(&actual, buffer, WIDTH, HEIGHT).unwrap();
+        panic!("{}: {} pixels differ from the reference, the output was written to {}", name, different, actual.display());
+    }
+}
+
+#[test]
+fn textured_walls() {
+    let world = create_room((8, 8), &[(5, 2), (4, 5)]);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+    caster.turn_by(0.3);
+
+    check_scene("textured_walls", &caster, &world);
+}
+
+#[test]
+fn sprites() {
+    let mut world = create_room((8, 8), &[(6, 2)]);
+    world.add_sprite(Sprite::new(4.5, 3.5, Tile::textured(0, tile_rect(3))));
+
+    let mut sprite = Sprite::new(3.5, 4.2, Tile::textured(0, tile_rect(3)));
+    sprite.set_scale(0.5);
+    sprite.z = 0.25;
+    world.add_sprite(sprite);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.8);
+    caster.face_east();
+
+    check_scene("sprites", &caster, &world);
+}
+
+#[test]
+fn translucent_sprites() {
+    let mut world = create_room((8, 8), &[(6, 3)]);
+    world.add_sprite(Sprite::new(4.5, 3.7, Tile::textured(0, tile_rect(3))));
+
+    // A ghost in front of the other sprite and the pillar, both stay visible through it
+    let mut ghost = Sprite::new(3.2, 3.4, Tile::textured(0, tile_rect(3)));
+    ghost.opacity = 0.4;
+    world.add_sprite(ghost);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+
+    check_scene("translucent_sprites", &caster, &world);
+}
+
+#[test]
+fn fog() {
+    let mut world = create_room((20, 4), &[]);
+    world.set_fog([120, 120, 150, 255], 6.0);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 1.8);
+    caster.face_east();
+
+    check_scene("fog", &caster, &world);
+}
+
+/// Creates a dark room with colored lights
+fn create_lit_room() -> WorldMap {
+    let mut world = create_room((8, 8), &[(5, 5)]);
+    world.set_fog([0, 0, 0, 255], 8.0);
+    world.set_ambient(0.3);
+
+    world.add_colored_light(3, 3, 3, [255, 80, 20, 255]);
+    world.add_colored_light(6, 3, 2, [40, 80, 255, 255]);
+    world.add_light(3, 5, 2);
+
+    // The flicker is seeded, the rendering is the same for every run
+    world.set_seed(7);
+    world.update_lights(1.0);
+
+    world
+}
+
+#[test]
+fn lighting() {
+    let world = create_lit_room();
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+    caster.set_smooth_lighting(false);
+
+    check_scene("lighting", &caster, &world);
+}
+
+#[test]
+fn smooth_lighting() {
+    let world = create_lit_room();
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+
+    check_scene("smooth_lighting", &caster, &world);
+}
+
+#[test]
+fn render_parity() {
+    let mut world = create_room((8, 8), &[(5, 2)]);
+    world.set_wall(3, 5, Tile::textured(0, tile_rect(3)));
+    world.set_wall_transparent(3, 5, true);
+    world.set_wall_height(6, 4, 1.5);
+    world.set_floor(2, 2, Tile::colored([200, 40, 40, 255]));
+    world.add_sprite(Sprite::new(4.5, 3.5, Tile::textured(0, tile_rect(3))));
+    world.add_light(2, 3, 1);
+    world.compute_lighting();
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 4.2);
+    caster.face_east();
+    caster.turn_by(-0.2);
+    caster.set_pitch(0.1);
+
+    // Render into a rect inside a larger frame, the pixels outside of the rect must stay untouched
+    let (frame_width, frame_height) = (WIDTH + 30, HEIGHT + 20);
+    let rect = (17, 9, WIDTH, HEIGHT);
+
+    let mut st = vec![77; frame_width * frame_height * 4];
+    caster.render_st(&mut st[..], rect, frame_width, &world);
+
+    let mut mt = vec![77; frame_width * frame_height * 4];
+    caster.render_mt(&mut mt[..], rect, frame_width, &world);
+
+    assert!(st == mt, "render_st and render_mt differ");
+
+    for (index, pixel) in st.chunks_exact(4).enumerate() {
+        let (x, y) = (index % frame_width, index / frame_width);
+        let inside = x >= rect.0 && x < rect.0 + rect.2 && y >= rect.1 && y < rect.1 + rect.3;
+        assert_eq!(inside, pixel != [77, 77, 77, 77], "pixel {}, {} inside: {}", x, y, inside);
+    }
+}
+
+#[test]
+fn bilinear_filter() {
+    let world = create_room((20, 4), &[(6, 2)]);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 1.4);
+    caster.face_east();
+    caster.set_filter(Filter::Bilinear);
+
+    check_scene("bilinear_filter", &caster, &world);
+}
+
+#[test]
+fn mipmap_filter() {
+    let world = create_room((20, 4), &[(6, 2)]);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 1.4);
+    caster.face_east();
+    caster.set_filter(Filter::Mipmap);
+
+    check_scene("mipmap_filter", &caster, &world);
+}
+
+#[test]
+fn decals() {
+    let mut world = create_room((8, 8), &[(4, 2)]);
+
+    // A checkered painting on the far wall, a round sign on the pillar and a round splat on the floor, partly hidden by the pillar
+    world.add_decal(7, 3, Decal::wall(Face::West, Tile::textured(0, tile_rect(1)), (0.2, 0.3, 0.6, 0.5)));
+    world.add_decal(4, 2, Decal::wall(Face::West, Tile::textured(0, tile_rect(3)), (0.1, 0.1, 0.8, 0.8)));
+    world.add_decal(3, 3, Decal::floor(Tile::textured(0, tile_rect(3)), (0.0, 0.0, 1.0, 1.0)));
+    world.add_decal(5, 2, Decal::floor(Tile::colored([200, 30, 30, 160]), (0.25, 0.25, 0.5, 0.5)));
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+    caster.turn_by(-15.0);
+
+    check_scene("decals", &caster, &world);
+}
+
+/// Creates a procedural sky panorama, a blue gradient with a row of hills at the horizon
+fn create_sky() -> (Vec<u8>, u32, u32) {
+    let (width, height) = (64, 16);
+    let mut data = vec![0; width * height * 4];
+
+    for y in 0..height {
+        for x in 0..width {
+            let hill = 12 - ((x as f32 / width as f32 * std::f32::consts::PI * 6.0).sin() * 2.0) as usize;
+            let color = if y >= hill { [40, 90, 40, 255] } else { [60 + (y * 8) as u8, 120 + (y * 6) as u8, 230, 255] };
+            let off = (x + y * width) * 4;
+            data[off..off + 4].copy_from_slice(&color);
+        }
+    }
+
+    (data, width as u32, height as u32)
+}
+
+#[test]
+fn sky() {
+    let mut world = create_room((8, 8), &[(5, 5)]);
+    world.set_fog([150, 150, 170, 255], 8.0);
+
+    let (data, width, height) = create_sky();
+    let sky_id = world.add_image(data, width, height);
+    world.set_sky(Tile::textured(sky_id, (0, 0, width as usize, height as usize)));
+
+    // Open the ceiling of the cells in front of the camera
+    for y in 2..6 {
+        for x in 3..6 {
+            world.set_ceiling_open(x, y, true);
+        }
+    }
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(1.5, 3.5);
+    caster.face_east();
+    caster.set_pitch(0.2);
+
+    check_scene("sky", &caster, &world);
+}
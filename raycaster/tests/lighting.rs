@@ -0,0 +1,239 @@
+//! Light propagation and flicker tests.
+
+use raycaster::prelude::*;
+
+/// Creates two rooms separated by a wall along x = 5 with an opening at (5, 8)
+fn create_rooms() -> WorldMap {
+    let mut world = WorldMap::new();
+
+    for y in 0..10 {
+        if y != 8 {
+            world.set_wall(5, y, Tile::colored([128, 128, 128, 255]));
+        }
+    }
+
+    world
+}
+
+#[test]
+fn walls_stop_light() {
+    let mut world = create_rooms();
+    world.add_light(3, 3, 5);
+    world.compute_lighting();
+
+    assert!(world.light_map.contains_key(&(4, 3)));
+    assert!(world.light_map.contains_key(&(5, 3)), "the wall itself is lit");
+    assert!(!world.light_map.contains_key(&(6, 3)), "the room behind the wall is dark");
+}
+
+/// Renders the wall along x = 5 lit by a light at (3, 3) from the given side and returns the color of the center pixel
+fn render_wall(x: f32, west: bool, smooth_lighting: bool) -> [u8; 3] {
+    let mut world = WorldMap::new();
+    for y in 0..10 {
+        world.set_wall(5, y, Tile::colored([200, 200, 200, 255]));
+    }
+    world.set_fog([0, 0, 0, 255], 1000.0);
+    world.set_ambient(0.0);
+    world.add_light(3, 3, 5);
+    world.compute_lighting();
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(x, 3.5);
+    if west { caster.face_west() } else { caster.face_east() }
+    caster.set_smooth_lighting(smooth_lighting);
+
+    let buffer = caster.render_to_buffer(40, 40, &world);
+    let off = (20 * 40 + 20) * 4;
+    [buffer[off], buffer[off + 1], buffer[off + 2]]
+}
+
+#[test]
+fn walls_are_dark_on_their_unlit_side() {
+    for smooth_lighting in [false, true] {
+        assert!(render_wall(3.5, false, smooth_lighting)[0] > 0, "the lit side");
+        assert_eq!(render_wall(7.5, true, smooth_lighting), [0, 0, 0], "the side facing the dark room");
+    }
+}
+
+#[test]
+fn smooth_light_does_not_pass_walls() {
+    let mut world = create_rooms();
+    world.set_ambient(0.0);
+    world.add_light(3, 3, 5);
+    world.compute_lighting();
+
+    assert_eq!(world.sample_light(6.1, 3.5), [0.0, 0.0, 0.0]);
+    assert!(world.sample_light(4.9, 3.5)[0] > 0.0);
+
+    // between two lit cells the light is still interpolated
+    let (a, b) = (world.get_light(3, 3)[0], world.get_light(4, 3)[0]);
+    assert!((world.sample_light(4.0, 3.5)[0] - (a + b) / 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn light_leaks_through_openings() {
+    let mut world = create_rooms();
+    world.add_light(4, 7, 5);
+    world.compute_lighting();
+
+    assert!(world.light_map.contains_key(&(5, 8)));
+    assert!(world.light_map.contains_key(&(6, 8)));
+    assert!(world.light_map.contains_key(&(6, 7)));
+    assert!(!world.light_map.contains_key(&(6, 5)));
+}
+
+#[test]
+fn transparent_walls_and_open_doors_pass_light() {
+    let mut world = create_rooms();
+    world.set_wall_transparent(5, 3, true);
+    world.set_door(5, 8, Door::new(Tile::colored([90, 60, 30, 255]), true));
+    world.add_light(3, 3, 5);
+    world.add_light(3, 8, 5);
+    world.compute_lighting();
+
+    assert!(world.light_map.contains_key(&(6, 3)));
+    assert!(!world.light_map.contains_key(&(7, 8)), "the closed door stops the light");
+
+    world.open_door(5, 8);
+    world.update_doors(1.0);
+    world.compute_lighting();
+
+    assert!(world.light_map.contains_key(&(7, 8)));
+}
+
+#[test]
+fn map_changes_update_the_lighting() {
+    let mut world = WorldMap::new();
+    world.add_light(2, 2, 4);
+    world.update_lights(0.0);
+    assert!(world.light_map.contains_key(&(4, 2)));
+
+    // walls and doors added after the light stop it on the next update
+    for y in 0..5 {
+        if y != 2 {
+            world.set_wall(3, y, Tile::colored([128, 128, 128, 255]));
+        }
+    }
+    world.update_lights(0.0);
+    assert!(world.light_map.contains_key(&(4, 2)), "the light leaks through the opening");
+
+    world.set_door(3, 2, Door::new(Tile::colored([90, 60, 30, 255]), true));
+    world.update_lights(0.0);
+    assert!(!world.light_map.contains_key(&(4, 2)));
+    assert_eq!(world.get_light(4, 2), [1.0, 1.0, 1.0]);
+
+    world.set_wall_transparent(3, 1, true);
+    world.update_lights(0.0);
+    assert!(world.light_map.contains_key(&(4, 1)), "the light passes the transparent wall");
+
+    world.set_wall_transparent(3, 1, false);
+    world.update_lights(0.0);
+    assert!(!world.light_map.contains_key(&(4, 1)), "the opaque wall stops the light");
+}
+
+#[test]
+fn colored_lights_add_to_the_ambient_level() {
+    let mut world = create_rooms();
+    world.set_ambient(0.5);
+    world.add_colored_light(2, 2, 2, [255, 0, 255, 255]);
+    world.compute_lighting();
+
+    assert_eq!(world.get_light(3, 2), [1.0, 0.5, 1.0]);
+    assert_eq!(world.get_light(2, 2), [1.0, 0.5, 1.0], "the light is capped at 1.0");
+    assert_eq!(world.get_light(7, 7), [0.5, 0.5, 0.5]);
+}
+
+#[test]
+fn lights_keep_the_default_look() {
+    let mut world = create_rooms();
+    world.set_default_floor(Tile::colored([100, 100, 100, 255]));
+    world.set_fog([0, 0, 0, 255], 1000.0);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(2.5, 2.5);
+    caster.face_east();
+    let unlit = caster.render_to_buffer(40, 40, &world);
+
+    // a torch next to the camera at the default ambient level of 1.0
+    world.add_light(2, 2, 2);
+    world.compute_lighting();
+
+    assert_eq!(world.get_light(2, 2), [1.0, 1.0, 1.0]);
+    assert_eq!(world.get_light(3, 2), [1.0, 1.0, 1.0]);
+    assert_eq!(caster.render_to_buffer(40, 40, &world), unlit);
+}
+
+#[test]
+fn steady_lights_do_not_flicker() {
+    let mut world = create_rooms();
+    world.add_light(2, 2, 3);
+    world.set_light_flicker(2, 2, 0.0, 0.0);
+
+    for _ in 0..10 {
+        world.update_lights(0.1);
+        assert_eq!(world.light_map.get(&(3, 3)), Some(&[0.5, 0.5, 0.5]));
+        assert_eq!(world.light_map.get(&(4, 4)), Some(&[0.25, 0.25, 0.25]));
+    }
+}
+
+#[test]
+fn seeded_flicker_is_reproducible() {
+    let create = || {
+        let mut world = create_rooms();
+        world.add_light(2, 2, 4);
+        world.add_colored_light(7, 6, 3, [255, 100, 0, 255]);
+        world.set_light_flicker(7, 6, 0.3, 10.0);
+        world.set_seed(42);
+        world
+    };
+
+    let mut a = create();
+    let mut b = create();
+
+    let mut changed = false;
+    for _ in 0..20 {
+        let before = a.light_map.clone();
+
+        a.update_lights(0.05);
+        b.update_lights(0.05);
+        assert_eq!(a.light_map, b.light_map);
+
+        changed |= a.light_map != before;
+    }
+    assert!(changed, "the lights flicker");
+}
+
+#[test]
+fn colored_default_floor_is_lit() {
+    let mut world = WorldMap::new();
+    world.set_default_floor(Tile::colored([100, 100, 100, 255]));
+    world.set_default_ceiling(Tile::colored([100, 100, 100, 255]));
+    world.set_fog([0, 0, 0, 255], 1000.0);
+    world.set_ambient(0.5);
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(5.5, 5.5);
+    caster.face_east();
+
+    let floor = |world: &WorldMap| {
+        let buffer = caster.render_to_buffer(40, 40, world);
+        let off = (39 * 40 + 20) * 4;
+        [buffer[off], buffer[off + 1], buffer[off + 2]]
+    };
+    let ceiling = |world: &WorldMap| {
+        let buffer = caster.render_to_buffer(40, 40, world);
+        let off = 20 * 4;
+        [buffer[off], buffer[off + 1], buffer[off + 2]]
+    };
+
+    // the ambient level darkens the colored floor and ceiling, the fog is too far away to matter
+    for color in [floor(&world), ceiling(&world)] {
+        assert!(color.iter().all(|c| (48..=50).contains(c)), "{:?}", color);
+    }
+
+    world.add_colored_light(5, 5, 3, [255, 0, 0, 255]);
+    world.compute_lighting();
+
+    let color = floor(&world);
+    assert!(color[0] > 60 && color[1] <= 50, "the red light tints the floor: {:?}", color);
+}
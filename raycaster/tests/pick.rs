@@ -0,0 +1,159 @@
+//! Screen space picking tests.
+
+use raycaster::prelude::*;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 100;
+
+/// Creates a room from 0 to 7 with gray walls and a door at (7, 5), the floor and the ceiling are colored
+fn create_room() -> WorldMap {
+    let mut world = WorldMap::new();
+    let tile = Tile::colored([128, 128, 128, 255]);
+
+    for i in 0..8 {
+        world.set_wall(i, 0, tile.clone());
+        world.set_wall(i, 7, tile.clone());
+        world.set_wall(0, i, tile.clone());
+        if i != 5 {
+            world.set_wall(7, i, tile.clone());
+        }
+    }
+    world.set_door(7, 5, Door::new(Tile::colored([90, 60, 30, 255]), true));
+
+    world.set_default_floor(Tile::colored([40, 40, 40, 255]));
+    world.set_default_ceiling(Tile::colored([60, 60, 80, 255]));
+
+    world
+}
+
+/// Adds a red sprite with transparent corners
+fn add_sprite(world: &mut WorldMap, x: f32, y: f32) -> SpriteId {
+    let mut data = vec![];
+    for y in 0..4 {
+        for x in 0..4 {
+            let corner = (x == 0 || x == 3) && (y == 0 || y == 3);
+            data.extend_from_slice(if corner { &[0, 0, 0, 0] } else { &[255, 0, 0, 255] });
+        }
+    }
+    let image_id = world.add_image(data, 4, 4);
+    world.add_sprite(Sprite::new(x, y, Tile::textured(image_id, (0, 0, 4, 4))))
+}
+
+fn create_caster() -> Raycaster {
+    create_caster_at(1.5, 3.5)
+}
+
+fn create_caster_at(x: f32, y: f32) -> Raycaster {
+    let mut caster = Raycaster::new();
+    caster.set_pos(x, y);
+    caster.face_east();
+    caster
+}
+
+#[test]
+fn pick_walls_floors_and_ceilings() {
+    let world = create_room();
+    let caster = create_caster();
+    let rect = (0, 0, WIDTH, HEIGHT);
+
+    let hit = caster.pick(WIDTH / 2, HEIGHT / 2 - 2, rect, &world).unwrap();
+    assert_eq!(hit.target, PickTarget::Wall(Face::West));
+    assert_eq!(hit.cell, (7, 3));
+    assert!((hit.position.0 - 7.0).abs() < 1e-4 && hit.position.2 > 0.5 && hit.position.2 < 1.0, "{:?}", hit.position);
+    assert!((hit.distance - 5.5).abs() < 1e-4);
+
+    let hit = caster.pick(WIDTH / 2, HEIGHT - 1, rect, &world).unwrap();
+    assert_eq!(hit.target, PickTarget::Floor);
+    assert_eq!(hit.cell, (2, 3));
+    assert_eq!(hit.position.2, 0.0);
+
+    let hit = caster.pick(WIDTH / 2, 0, rect, &world).unwrap();
+    assert_eq!(hit.target, PickTarget::Ceiling);
+    assert_eq!(hit.position.2, 1.0);
+
+    assert!(caster.pick(WIDTH / 2, HEIGHT / 2, rect, &world).unwrap().target != PickTarget::Floor, "the horizon row is a wall");
+    assert!(caster.pick(WIDTH, 0, rect, &world).is_none());
+
+    // the door on the left of the view
+    let mut caster = create_caster();
+    caster.turn_by(-15.0);
+    let door = (0..WIDTH).filter_map(|x| caster.pick(x, HEIGHT / 2, rect, &world)).find(|hit| hit.target == PickTarget::Door).unwrap();
+    assert_eq!(door.cell, (7, 5));
+    assert!((door.position.0 - 7.5).abs() < 1e-4, "the door is recessed half a cell");
+}
+
+#[test]
+fn pick_in_an_offset_rect() {
+    let world = create_room();
+    let caster = create_caster();
+
+    let rect = (17, 9, WIDTH, HEIGHT);
+    for (x, y) in [(0, 0), (WIDTH / 2, HEIGHT / 2 - 2), (WIDTH - 1, HEIGHT - 1), (30, 70)] {
+        assert_eq!(caster.pick(x + 17, y + 9, rect, &world), caster.pick(x, y, (0, 0, WIDTH, HEIGHT), &world));
+    }
+
+    assert!(caster.pick(16, 50, rect, &world).is_none());
+    assert!(caster.pick(50, 8, rect, &world).is_none());
+    assert!(caster.pick(17 + WIDTH, 50, rect, &world).is_none());
+}
+
+#[test]
+fn pick_sprites_where_they_are_drawn() {
+    let mut world = create_room();
+    let near = add_sprite(&mut world, 3.5, 3.3);
+    let far = add_sprite(&mut world, 5.5, 3.8);
+    // a sprite behind the wall is never picked
+    add_sprite(&mut world, 8.5, 3.5);
+
+    let caster = create_caster();
+    let rect = (0, 0, WIDTH, HEIGHT);
+
+    // the sprites are drawn where the frame differs from the room without sprites
+    let background = caster.render_to_buffer(WIDTH, HEIGHT, &create_room());
+    let frame = caster.render_to_buffer(WIDTH, HEIGHT, &world);
+
+    let mut picked = [0, 0];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let off = (x + y * WIDTH) * 4;
+            let drawn = frame[off..off + 4] != background[off..off + 4];
+            let hit = caster.pick(x, y, rect, &world).unwrap();
+
+            match hit.target {
+                PickTarget::Sprite(id) => {
+                    assert!(drawn, "sprite picked at {}, {} where it is not drawn", x, y);
+                    picked[if id == near { 0 } else { assert_eq!(id, far); 1 }] += 1;
+                    assert!(hit.position.2 >= 0.0 && hit.position.2 <= 1.0);
+                },
+                _ => assert!(!drawn, "sprite drawn at {}, {} but not picked", x, y),
+            }
+        }
+    }
+
+    assert!(picked[0] > picked[1] && picked[1] > 0, "{:?}", picked);
+}
+
+#[test]
+fn pick_stops_where_the_renderer_stops() {
+    // a corridor with a wall at its end which is too far away to be drawn
+    let create_corridor = |end_wall: bool| {
+        let mut world = WorldMap::new();
+        let tile = Tile::colored([128, 128, 128, 255]);
+        for x in 0..56 {
+            world.set_wall(x, 0, tile.clone());
+            world.set_wall(x, 2, tile.clone());
+        }
+        world.set_wall(0, 1, tile.clone());
+        if end_wall {
+            world.set_wall(55, 1, tile);
+        }
+        world
+    };
+
+    let world = create_corridor(true);
+    let caster = create_caster_at(1.5, 1.5);
+    let rect = (0, 0, WIDTH, HEIGHT);
+
+    assert_eq!(caster.render_to_buffer(WIDTH, HEIGHT, &world), caster.render_to_buffer(WIDTH, HEIGHT, &create_corridor(false)), "the end wall is not drawn");
+    assert!(caster.pick(WIDTH / 2, HEIGHT / 2, rect, &world).is_none(), "the end wall is not picked");
+}
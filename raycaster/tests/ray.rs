@@ -0,0 +1,140 @@
+//! Raycast query and line of sight tests.
+
+use raycaster::prelude::*;
+
+/// Creates a room from 0 to 9 with a wall at x = 5, a transparent wall at (5, 2), a door at (5, 6) and an opening at (5, 8)
+fn create_room() -> WorldMap {
+    let mut world = WorldMap::new();
+    let tile = Tile::colored([128, 128, 128, 255]);
+
+    for i in 0..10 {
+        world.set_wall(i, 0, tile.clone());
+        world.set_wall(i, 9, tile.clone());
+        world.set_wall(0, i, tile.clone());
+        world.set_wall(9, i, tile.clone());
+    }
+
+    for y in 1..9 {
+        match y {
+            6 => world.set_door(5, y, Door::new(tile.clone(), true)),
+            8 => {},
+            _ => world.set_wall(5, y, tile.clone()),
+        }
+    }
+    world.set_wall_transparent(5, 2, true);
+
+    world
+}
+
+#[test]
+fn rays_hit_walls() {
+    let world = create_room();
+
+    let hit = world.cast_ray((2.5, 3.5), (1.0, 0.0), 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (5, 3));
+    assert_eq!(hit.face, Face::West);
+    assert_eq!(hit.point, (5.0, 3.5));
+    assert_eq!(hit.distance, 2.5);
+    assert!(!hit.door);
+
+    // the direction does not need to be normalized
+    let hit = world.cast_ray((2.5, 3.5), (0.0, -3.0), 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (2, 0));
+    assert_eq!(hit.face, Face::North);
+    assert_eq!(hit.distance, 2.5);
+
+    // diagonal rays
+    let hit = world.cast_ray((1.5, 1.5), (-1.0, -1.0), 100.0).wall.unwrap();
+    assert!((hit.distance - 0.5 * 2.0f32.sqrt()).abs() < 1e-5);
+
+    // walls beyond the maximum distance are not hit
+    assert!(world.cast_ray((2.5, 3.5), (1.0, 0.0), 2.0).wall.is_none());
+}
+
+#[test]
+fn rays_hit_the_closed_part_of_doors() {
+    let mut world = create_room();
+
+    let hit = world.cast_ray((2.5, 6.5), (1.0, 0.0), 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (5, 6));
+    assert!(hit.door);
+    assert_eq!(hit.distance, 3.0, "the door is recessed half a cell");
+
+    world.open_door(5, 6);
+    world.update_doors(1.0);
+
+    let hit = world.cast_ray((2.5, 6.5), (1.0, 0.0), 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (9, 6));
+    assert_eq!(hit.face, Face::West);
+}
+
+#[test]
+fn rays_hit_sprites_in_front_of_walls() {
+    let mut world = create_room();
+    let near = world.add_sprite(Sprite::new(3.5, 3.5, Tile::colored([255, 0, 0, 255])));
+    world.add_sprite(Sprite::new(4.5, 3.5, Tile::colored([255, 0, 0, 255])));
+    world.add_sprite(Sprite::new(7.5, 3.5, Tile::colored([255, 0, 0, 255])));
+
+    let result = world.cast_ray((1.5, 3.5), (1.0, 0.0), 100.0);
+    let sprite = result.sprite.unwrap();
+    assert_eq!(sprite.id, near);
+    assert_eq!(sprite.distance, 1.5);
+    assert_eq!(sprite.point, (3.0, 3.5));
+    assert_eq!(result.wall.unwrap().cell, (5, 3));
+
+    // rays passing beside the sprite miss it, the sprite behind the wall is never reported
+    let result = world.cast_ray((1.5, 4.1), (1.0, 0.0), 100.0);
+    assert!(result.sprite.is_none());
+
+    // a sprite casting from its own position does not hit itself
+    let result = world.cast_ray((3.5, 3.5), (1.0, 0.0), 100.0);
+    assert!(result.sprite.is_some_and(|hit| hit.id != near));
+}
+
+#[test]
+fn line_of_sight() {
+    let mut world = create_room();
+
+    assert!(world.line_of_sight((1.5, 3.5), (4.5, 3.5)));
+    assert!(!world.line_of_sight((1.5, 3.5), (7.5, 3.5)), "the wall blocks the sight");
+    assert!(world.line_of_sight((1.5, 2.5), (7.5, 2.5)), "transparent walls do not block the sight");
+    assert!(world.line_of_sight((3.5, 8.5), (7.5, 8.5)), "through the opening");
+    assert!(world.line_of_sight((7.5, 8.5), (3.5, 8.5)), "in both directions");
+    assert!(world.line_of_sight((2.5, 2.5), (2.5, 2.5)));
+
+    // sprites do not block the sight
+    world.add_sprite(Sprite::new(3.5, 3.5, Tile::colored([255, 0, 0, 255])));
+    assert!(world.line_of_sight((1.5, 3.5), (4.5, 3.5)));
+
+    assert!(!world.line_of_sight((2.5, 6.5), (7.5, 6.5)), "the closed door blocks the sight");
+    world.open_door(5, 6);
+    world.update_doors(1.0);
+    assert!(world.line_of_sight((2.5, 6.5), (7.5, 6.5)));
+}
+
+#[test]
+fn view_ray() {
+    let world = create_room();
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(2.5, 3.5);
+    caster.face_east();
+
+    let hit = caster.cast_view_ray(&world, 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (5, 3));
+    assert_eq!(hit.distance, 2.5);
+}
+
+#[test]
+fn queries_reach_far_walls() {
+    let mut world = WorldMap::new();
+    world.set_wall(25, 25, Tile::colored([128, 128, 128, 255]));
+    world.set_wall(50, 0, Tile::colored([128, 128, 128, 255]));
+
+    assert!(!world.line_of_sight((0.5, 0.5), (30.5, 30.5)), "the wall blocks the sight beyond the view distance");
+    assert!(world.line_of_sight((0.5, 0.5), (24.5, 24.5)));
+
+    let hit = world.cast_ray((0.5, 0.5), (1.0, 0.0), 100.0).wall.unwrap();
+    assert_eq!(hit.cell, (50, 0));
+    assert!(world.cast_ray((0.5, 0.5), (1.0, 0.0), 49.0).wall.is_none());
+}
@@ -0,0 +1,255 @@
+//! Sprite tests.
+
+use raycaster::prelude::*;
+
+/// Creates the rotation tiles, each one has a unique color
+fn create_rotations(count: u8) -> Vec<Tile> {
+    (0..count).map(|i| Tile::colored([i, 0, 0, 255])).collect()
+}
+
+#[test]
+fn directional_sprite_views() {
+    // a sprite at the origin facing east
+    let sprite = Sprite::directional(0.0, 0.0, create_rotations(8), 0.0).unwrap();
+
+    assert_eq!(sprite.get_tile(5.0, 0.0), &sprite.rotations[0], "front");
+    assert_eq!(sprite.get_tile(5.0, 5.0), &sprite.rotations[1], "front left");
+    assert_eq!(sprite.get_tile(0.0, 5.0), &sprite.rotations[2], "left");
+    assert_eq!(sprite.get_tile(-5.0, 0.0), &sprite.rotations[4], "back");
+    assert_eq!(sprite.get_tile(0.0, -5.0), &sprite.rotations[6], "right");
+    assert_eq!(sprite.get_tile(5.0, -0.5), &sprite.rotations[0], "close to the front");
+}
+
+#[test]
+fn directional_sprite_facing() {
+    // the same sprite facing north shows its back to a viewer in the south
+    let sprite = Sprite::directional(2.0, 2.0, create_rotations(8), 90.0).unwrap();
+
+    assert_eq!(sprite.get_tile(2.0, 8.0), &sprite.rotations[0]);
+    assert_eq!(sprite.get_tile(2.0, -3.0), &sprite.rotations[4]);
+    assert_eq!(sprite.get_tile(8.0, 2.0), &sprite.rotations[6]);
+}
+
+#[test]
+fn sprites_without_rotations_use_their_tile() {
+    let sprite = Sprite::new(1.0, 1.0, Tile::colored([1, 2, 3, 255]));
+
+    assert_eq!(sprite.get_tile(-4.0, 3.0), &sprite.tile);
+    assert!(Sprite::directional(1.0, 1.0, vec![], 0.0).is_none());
+}
+
+/// Returns the frames an animation shows for a tile with 4 frames at the given times
+fn frames_at(mut animation: Animation, times: &[f32]) -> Vec<usize> {
+    let mut frames = vec![];
+    let mut last = 0.0;
+    for time in times {
+        animation.update(time - last);
+        last = *time;
+        frames.push(animation.get_frame(4));
+    }
+    frames
+}
+
+#[test]
+fn animation_play_modes() {
+    let times = [0.0, 0.15, 0.25, 0.35, 0.45, 0.55, 0.65, 0.75, 0.85];
+
+    assert_eq!(frames_at(Animation::new(0.1, PlayMode::Loop), &times), vec![0, 1, 2, 3, 0, 1, 2, 3, 0]);
+    assert_eq!(frames_at(Animation::new(0.1, PlayMode::Once), &times), vec![0, 1, 2, 3, 3, 3, 3, 3, 3]);
+    assert_eq!(frames_at(Animation::new(0.1, PlayMode::PingPong), &times), vec![0, 1, 2, 3, 2, 1, 0, 1, 2]);
+
+    let mut delayed = Animation::new(0.1, PlayMode::Loop);
+    delayed.offset = -0.2;
+    assert_eq!(frames_at(delayed, &times), vec![0, 0, 0, 1, 2, 3, 0, 1, 2]);
+}
+
+#[test]
+fn one_shot_animation_finishes() {
+    let mut world = WorldMap::new();
+
+    let mut sprite = Sprite::new(1.0, 1.0, Tile::textured_anim(0, (0, 0, 16, 16), 4));
+    sprite.play(Animation::new(0.1, PlayMode::Once));
+    let id = world.add_sprite(sprite);
+
+    world.update_sprites(0.35);
+    assert!(!world.get_sprite(id).unwrap().is_animation_finished(0.0, 0.0));
+
+    world.update_sprites(0.1);
+    assert!(world.get_sprite(id).unwrap().is_animation_finished(0.0, 0.0));
+
+    world.get_sprite_mut(id).unwrap().animation.as_mut().unwrap().restart();
+    assert!(!world.get_sprite(id).unwrap().is_animation_finished(0.0, 0.0));
+}
+
+#[test]
+fn directional_animation_finishes_with_the_shown_view() {
+    // the front view has 2 frames, the back view 6
+    let rotations = vec![Tile::textured_anim(0, (0, 0, 16, 16), 2), Tile::textured_anim(0, (0, 16, 16, 16), 6)];
+    let mut sprite = Sprite::directional(0.0, 0.0, rotations, 0.0).unwrap();
+    sprite.play(Animation::new(0.1, PlayMode::Once));
+    sprite.animation.as_mut().unwrap().update(0.3);
+
+    assert!(sprite.is_animation_finished(5.0, 0.0), "seen from the front");
+    assert!(!sprite.is_animation_finished(-5.0, 0.0), "seen from the back");
+}
+
+#[test]
+fn sprite_ids_stay_valid_after_removal() {
+    let mut world = WorldMap::new();
+    let tile = Tile::colored([255, 0, 0, 255]);
+
+    let a = world.add_sprite(Sprite::new(1.0, 1.0, tile.clone()));
+    let b = world.add_sprite(Sprite::new(2.0, 2.0, tile.clone()));
+    let c = world.add_sprite(Sprite::new(3.0, 3.0, tile.clone()));
+
+    assert_eq!(world.remove_sprite(a).map(|sprite| sprite.x), Some(1.0));
+    assert!(world.remove_sprite(a).is_none());
+    assert!(world.get_sprite(a).is_none());
+
+    world.get_sprite_mut(c).unwrap().x = 5.0;
+    assert_eq!(world.get_sprite(b).unwrap().x, 2.0);
+    assert_eq!(world.get_sprite(c).unwrap().x, 5.0);
+
+    // ids are not reused
+    let d = world.add_sprite(Sprite::new(4.0, 4.0, tile));
+    assert!(d != a && d != b && d != c);
+
+    let mut ids : Vec<SpriteId> = world.sprites().map(|(id, _)| id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![b, c, d]);
+    assert_eq!(world.sprite_count(), 3);
+
+    for (_, sprite) in world.sprites_mut() {
+        sprite.y += 1.0;
+    }
+    assert_eq!(world.get_sprite(d).unwrap().y, 5.0);
+}
+
+/// Renders a single white sprite on black and returns the rows and columns it covers in the image relative to its size
+fn sprite_bounds(sprite: Sprite, width: usize, height: usize) -> (f32, f32, f32, f32) {
+    let mut world = WorldMap::new();
+    let image_id = world.add_image(vec![255; 4 * 4 * 4], 4, 4);
+    world.add_sprite(Sprite { tile: Tile::textured(image_id, (0, 0, 4, 4)), ..sprite });
+
+    let mut caster = Raycaster::new();
+    caster.set_pos(0.5, 0.5);
+    caster.face_east();
+
+    let buffer = caster.render_to_buffer(width, height, &world);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+    for (index, pixel) in buffer.chunks_exact(4).enumerate() {
+        if pixel[0] > 0 {
+            let (x, y) = (index % width, index / width);
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+        }
+    }
+    (min_x as f32 / width as f32, min_y as f32 / height as f32, (max_x + 1) as f32 / width as f32, (max_y + 1) as f32 / height as f32)
+}
+
+#[test]
+fn sprite_placement_is_resolution_independent() {
+    let mut sprite = Sprite::new(3.5, 0.5, Tile::colored([0, 0, 0, 0]));
+    sprite.scale_x = 0.25;
+    sprite.scale_y = 0.5;
+    sprite.z = 0.6;
+
+    let small = sprite_bounds(sprite.clone(), 160, 100);
+    let large = sprite_bounds(sprite, 640, 400);
+
+    for (a, b) in [(small.0, large.0), (small.1, large.1), (small.2, large.2), (small.3, large.3)] {
+        assert!((a - b).abs() <= 0.02, "{:?} != {:?}", small, large);
+    }
+
+    // the sprite is half as wide as high and floats above the horizon in the middle of the screen
+    let (w, h) = ((small.2 - small.0) * 160.0, (small.3 - small.1) * 100.0);
+    assert!((w * 2.0 - h).abs() <= 2.0, "{} x {}", w, h);
+    assert!(small.3 < 0.5 && small.0 < 0.5 && small.2 > 0.5);
+}
+
+/// Renders an animated sprite with a red and a green frame and returns the index of the frame shown
+fn shown_frame(caster: &Raycaster) -> usize {
+    let mut world = WorldMap::new();
+    let image_id = world.add_image(vec![255, 0, 0, 255, 0, 255, 0, 255], 2, 1);
+    world.add_sprite(Sprite::new(2.5, 0.5, Tile::textured_anim(image_id, (0, 0, 1, 1), 2)));
+
+    let buffer = caster.render_to_buffer(40, 40, &world);
+    let off = (20 + 20 * 40) * 4;
+    if buffer[off] > 0 { 0 } else { assert!(buffer[off + 1] > 0); 1 }
+}
+
+#[test]
+fn global_animation_time() {
+    let mut world = WorldMap::new();
+    let mut caster = Raycaster::new();
+    caster.set_pos(0.5, 0.5);
+    caster.face_east();
+    caster.set_anim_time(100);
+
+    assert_eq!(shown_frame(&caster), 0);
+    caster.update(0.15, &mut world);
+    assert_eq!(shown_frame(&caster), 1);
+
+    // several frames in one update
+    caster.update(0.25, &mut world);
+    assert_eq!(shown_frame(&caster), 0);
+
+    // very large steps, for example after a pause, do not hang
+    caster.update(1.0e12, &mut world);
+    caster.update(f32::INFINITY, &mut world);
+
+    // an animation time of 0 stops the animation
+    caster.set_anim_time(0);
+    let frame = shown_frame(&caster);
+    caster.update(0.15, &mut world);
+    assert_eq!(shown_frame(&caster), frame);
+}
+
+#[test]
+fn sprites_show_through_translucent_walls() {
+    let render = |alpha: u8| {
+        let mut world = WorldMap::new();
+        world.set_fog([0, 0, 0, 255], 1000.0);
+        world.set_wall(1, 0, Tile::colored([0, 0, 255, alpha]));
+        world.set_wall_transparent(1, 0, true);
+        let image_id = world.add_image(vec![255, 0, 0, 255], 1, 1);
+        world.add_sprite(Sprite::new(2.5, 0.5, Tile::textured(image_id, (0, 0, 1, 1))));
+
+        let mut caster = Raycaster::new();
+        caster.set_pos(0.5, 0.5);
+        caster.face_east();
+
+        let buffer = caster.render_to_buffer(40, 40, &world);
+        let off = (20 * 40 + 20) * 4;
+        [buffer[off], buffer[off + 1], buffer[off + 2]]
+    };
+
+    assert!(render(128)[0] > 0, "the sprite is seen through the window");
+    assert_eq!(render(255)[0], 0, "opaque texels hide the sprite");
+}
+
+#[test]
+fn field_of_view_is_clamped() {
+    let mut world = WorldMap::new();
+    let image_id = world.add_image(vec![255, 0, 0, 255], 1, 1);
+    world.add_sprite(Sprite::new(3.5, 0.5, Tile::textured(image_id, (0, 0, 1, 1))));
+
+    let create_caster = |fovs: &[f32]| {
+        let mut caster = Raycaster::new();
+        caster.set_pos(0.5, 0.5);
+        caster.face_east();
+        for fov in fovs {
+            caster.set_fov(*fov);
+        }
+        caster
+    };
+
+    // invalid angles do not break later changes of the field of view
+    let expected = create_caster(&[90.0]).render_to_buffer(40, 40, &world);
+    for invalid in [0.0, -10.0, 180.0, 400.0, f32::NAN, f32::INFINITY] {
+        assert_eq!(create_caster(&[invalid, 90.0]).render_to_buffer(40, 40, &world), expected, "{}", invalid);
+    }
+
+    // the widest field of view still projects the sprite
+    let buffer = create_caster(&[180.0]).render_to_buffer(40, 40, &world);
+    assert!(buffer[(20 * 40 + 20) * 4] > 0);
+}